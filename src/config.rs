@@ -0,0 +1,97 @@
+use serde::Deserialize;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use toml::{Table, Value};
+
+// Every top-level key is a flag; `[icons.extensions]` and `[icons.filenames]` map names to glyphs.
+#[derive(Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub icons: IconTables,
+    #[serde(flatten)]
+    flags: Table,
+}
+
+#[derive(Deserialize, Default)]
+pub struct IconTables {
+    #[serde(default)]
+    pub extensions: Table,
+    #[serde(default)]
+    pub filenames: Table,
+}
+
+impl Config {
+    pub fn load() -> Option<Self> {
+        let path = config_path()?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => { eprintln!("Warning: could not read {}: {}", path.display(), e); return None; }
+        };
+        toml::from_str(&text).map_err(|e| eprintln!("Warning: ignoring {}: {}", path.display(), e.to_string().trim_end())).ok()
+    }
+
+    // Keys become flags in the order the file gives them: `long = true` becomes `--long`, `sort = "size"` becomes
+    // `--sort=size`, and an array repeats the flag once per item.
+    pub fn to_args(&self) -> Vec<OsString> {
+        let mut args = Vec::new();
+        for (key, value) in &self.flags {
+            let flag = format!("--{}", key.replace('_', "-"));
+            let values = match value { Value::Array(items) => items.clone(), other => vec![other.clone()] };
+            for value in values {
+                match value {
+                    Value::Boolean(true) => args.push(flag.clone().into()),
+                    Value::Boolean(false) => {}
+                    Value::Integer(i) => args.push(format!("{}={}", flag, i).into()),
+                    Value::Float(f) => args.push(format!("{}={}", flag, f).into()),
+                    Value::String(s) => args.push(format!("{}={}", flag, s).into()),
+                    _ => eprintln!("Warning: ignoring `{}` from the config file: expected a boolean, number, string or array of those", key),
+                }
+            }
+        }
+        args
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    #[cfg(unix)]
+    let base = env::var_os("XDG_CONFIG_HOME").filter(|v| !v.is_empty()).map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+    #[cfg(windows)]
+    let base = env::var_os("APPDATA").map(PathBuf::from);
+    base.map(|dir| dir.join("ls-pro").join("config.toml"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        toml::from_str::<Config>(text).unwrap().to_args().into_iter().map(|arg| arg.into_string().unwrap()).collect()
+    }
+
+    #[test]
+    fn flags_keep_the_order_of_the_file() {
+        assert_eq!(args("sort = 'size'\nlong = true\nall = false\ndepth = 2\nwatch = 0.5"), ["--sort=size", "--long", "--depth=2", "--watch=0.5"]);
+    }
+
+    #[test]
+    fn strings_may_hold_quotes_commas_and_hashes() {
+        assert_eq!(args("glob = [\"a\\\"#, b\", '*.rs'] # both"), ["--glob=a\"#, b", "--glob=*.rs"]);
+    }
+
+    #[test]
+    fn icon_tables_are_not_flags() {
+        let config: Config = toml::from_str("long = true\n[icons.extensions]\npy = 'P'").unwrap();
+        assert_eq!(config.icons.extensions.get("py"), Some(&Value::String("P".to_string())));
+        assert_eq!(config.to_args(), ["--long"]);
+    }
+
+    #[test]
+    fn invalid_toml_is_an_error() {
+        assert!(toml::from_str::<Config>("long = tru").is_err());
+        assert!(toml::from_str::<Config>("sort = \"size").is_err());
+    }
+}
@@ -1,12 +1,29 @@
 use crossterm::style::Color;
-use git2::{Error, Repository, Status};
-use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use git2::{BranchType, Error, Repository, Status};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 
 type Result<T> = std::result::Result<T, Error>;
 
+// The index (staged) and worktree (unstaged) status of a path, like the two columns of `git status --short`.
+pub type StatusPair = [(char, Color); 2];
+
+// The files of the repository holding `path` whose changes can change its statuses or branch.
+pub fn state_files(path: &Path) -> Vec<PathBuf> {
+    Repository::discover(path).map_or_else(|_| Vec::new(), |repo| vec![repo.path().join("index"), repo.path().join("HEAD")])
+}
+
+// Statuses are keyed by repository-relative paths. Entries are matched by their place under the listed
+// directory, whose own place in the repository is resolved once, so a symlinked workdir needs no per-entry lookups.
 pub struct GitStatusCache {
     statuses: HashMap<PathBuf, Status>,
+    submodules: HashSet<PathBuf>,
+    // Every directory holding a file in the index, which tells a tracked directory from a new one.
+    tracked_dirs: HashSet<PathBuf>,
+    workdir: PathBuf,
+    // The listed directory as an absolute path, and relative to the workdir.
+    root: Option<(PathBuf, PathBuf)>,
+    branch: Option<(String, usize, usize)>,
 }
 
 impl GitStatusCache {
@@ -14,45 +31,108 @@ impl GitStatusCache {
         match Repository::discover(path) {
             Ok(repo) => {
                 let mut status_opts = git2::StatusOptions::new();
-                status_opts.include_untracked(true).recurse_untracked_dirs(true);
+                status_opts.include_untracked(true).recurse_untracked_dirs(true).include_ignored(true);
                 let statuses = repo.statuses(Some(&mut status_opts))?;
-                let mut status_map = HashMap::new();
-
-                if let Some(repo_root) = repo.workdir() {
-                    for entry in statuses.iter() {
-                        if let Some(path_str) = entry.path() {
-                            let full_path = repo_root.join(path_str);
-                            if let Ok(canonical_path) = full_path.canonicalize() {
-                                status_map.insert(canonical_path, entry.status());
-                            } else {
-                                status_map.insert(full_path, entry.status());
-                            }
-                        }
-                    }
+                let Some(workdir) = repo.workdir() else { return Ok(None) };
+                let workdir = workdir.canonicalize().unwrap_or_else(|_| workdir.to_path_buf());
+                let status_map = statuses.iter().filter_map(|entry| Some((PathBuf::from(entry.path()?), entry.status()))).collect();
+                let submodules = repo.submodules().unwrap_or_default().iter().map(|sub| sub.path().to_path_buf()).collect();
+                let mut tracked_dirs = HashSet::new();
+                for entry in repo.index()?.iter() {
+                    let path = PathBuf::from(String::from_utf8_lossy(&entry.path).into_owned());
+                    tracked_dirs.extend(path.ancestors().skip(1).map(Path::to_path_buf));
                 }
-                Ok(Some(Self { statuses: status_map }))
+                let root = std::path::absolute(path).ok().zip(path.canonicalize().ok()
+                    .and_then(|canonical| canonical.strip_prefix(&workdir).ok().map(Path::to_path_buf)));
+                Ok(Some(Self { statuses: status_map, submodules, tracked_dirs, workdir, root, branch: Self::read_branch(&repo) }))
             }
             Err(_) => Ok(None),
         }
     }
 
-    pub fn get(&self, path: &Path) -> Option<(char, Color)> {
-        self.statuses.get(path).map(Self::status_to_char_color)
-    }
-
-    fn status_to_char_color(status: &Status) -> (char, Color) {
-        if status.is_index_new() { ('A', Color::Green) }
-        else if status.is_index_modified() { ('M', Color::Green) }
-        else if status.is_index_deleted() { ('D', Color::Red) }
-        else if status.is_index_renamed() { ('R', Color::Green) }
-        else if status.is_index_typechange() { ('T', Color::Green) }
-        else if status.is_wt_new() { ('?', Color::Cyan) }
-        else if status.is_wt_modified() { ('M', Color::Yellow) }
-        else if status.is_wt_deleted() { ('D', Color::Red) }
-        else if status.is_wt_renamed() { ('R', Color::Yellow) }
-        else if status.is_wt_typechange() { ('T', Color::Yellow) }
-        else if status.is_ignored() { ('I', Color::DarkGrey) }
-        else if status.is_conflicted() { ('C', Color::Red) }
-        else { (' ', Color::White) }
+    // The checked-out branch and how many commits it is ahead of and behind its upstream (0, 0 without one).
+    pub fn branch_info(&self) -> Option<(String, usize, usize)> {
+        self.branch.clone()
+    }
+
+    fn read_branch(repo: &Repository) -> Option<(String, usize, usize)> {
+        let head = repo.head().ok()?;
+        let name = head.shorthand()?.to_string();
+        let upstream = repo.find_branch(&name, BranchType::Local).and_then(|b| b.upstream()).ok();
+        let (ahead, behind) = match (head.target(), upstream.and_then(|u| u.get().target())) {
+            (Some(local), Some(upstream)) => repo.graph_ahead_behind(local, upstream).unwrap_or((0, 0)),
+            _ => (0, 0),
+        };
+        Some((name, ahead, behind))
+    }
+
+    // Paths outside the listed directory, like other operands or ones going through `..`, are resolved the slow way.
+    fn relative_path(&self, path: &Path) -> Option<PathBuf> {
+        if let Some((absolute_root, relative_root)) = &self.root
+            && let Ok(rest) = std::path::absolute(path).unwrap_or_default().strip_prefix(absolute_root)
+            && rest.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Some(relative_root.join(rest));
+        }
+        path.canonicalize().ok()?.strip_prefix(&self.workdir).ok().map(Path::to_path_buf)
+    }
+
+    // Ignored directories are reported as a whole, so anything inside one is ignored too. Submodules take an `S`
+    // in the staged column unless something is staged there, including one that is not yet in the index.
+    pub fn get(&self, path: &Path) -> Option<StatusPair> {
+        let path = self.relative_path(path)?;
+        if self.submodules.contains(&path) {
+            let [index, worktree] = self.statuses.get(&path).map_or([(' ', Color::White); 2], Self::status_to_chars);
+            return Some(if index.0 == ' ' || index.0 == '?' { [('S', Color::Magenta), worktree] } else { [index, worktree] });
+        }
+        self.statuses.get(&path).map(Self::status_to_chars).or_else(|| {
+            path.ancestors().skip(1).any(|dir| self.statuses.get(dir).is_some_and(|s| s.is_ignored()))
+                .then_some([('!', Color::DarkGrey); 2])
+        })
+    }
+
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        self.get(path).is_some_and(|[(index, _), _]| index == '!')
+    }
+
+    // A summary of everything under a directory: `M` in a column if any descendant has a change there,
+    // `??` if it only holds untracked files and `UU` if anything is conflicted. A tracked directory with
+    // untracked files in it shows them as `?` in the worktree column.
+    pub fn dir_summary(&self, dir: &Path) -> Option<StatusPair> {
+        let dir = self.relative_path(dir)?;
+        let (mut staged, mut unstaged, mut untracked, mut conflicted) = (false, false, false, false);
+        for status in self.statuses.iter().filter(|(path, _)| **path != dir && path.starts_with(&dir)).map(|(_, status)| status) {
+            let [index, worktree] = Self::status_to_chars(status);
+            match (index.0, worktree.0) {
+                ('U', _) => conflicted = true,
+                ('?', _) => untracked = true,
+                ('!', _) => {}
+                _ => { staged |= index.0 != ' '; unstaged |= worktree.0 != ' '; }
+            }
+        }
+        if conflicted { return Some([('U', Color::Red); 2]); }
+        if !staged && !unstaged && !untracked { return None; }
+        if !staged && !unstaged && !self.tracked_dirs.contains(&dir) { return Some([('?', Color::Cyan); 2]); }
+        Some([
+            if staged { ('M', Color::Green) } else { (' ', Color::White) },
+            if unstaged { ('M', Color::Yellow) } else if untracked { ('?', Color::Cyan) } else { (' ', Color::White) },
+        ])
+    }
+
+    fn status_to_chars(status: &Status) -> StatusPair {
+        if status.is_conflicted() { return [('U', Color::Red); 2]; }
+        if status.is_wt_new() { return [('?', Color::Cyan); 2]; }
+        if status.is_ignored() { return [('!', Color::DarkGrey); 2]; }
+        let index = if status.is_index_new() { ('A', Color::Green) }
+            else if status.is_index_modified() { ('M', Color::Green) }
+            else if status.is_index_deleted() { ('D', Color::Red) }
+            else if status.is_index_renamed() { ('R', Color::Green) }
+            else if status.is_index_typechange() { ('T', Color::Green) }
+            else { (' ', Color::White) };
+        let worktree = if status.is_wt_modified() { ('M', Color::Yellow) }
+            else if status.is_wt_deleted() { ('D', Color::Red) }
+            else if status.is_wt_renamed() { ('R', Color::Yellow) }
+            else if status.is_wt_typechange() { ('T', Color::Yellow) }
+            else { (' ', Color::White) };
+        [index, worktree]
     }
 }
\ No newline at end of file
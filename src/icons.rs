@@ -0,0 +1,74 @@
+use crate::config::Config;
+use toml::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+const DIR_ICON: &str = " ";
+pub const GIT_ICON: &str = " ";
+const DEFAULT_FILE_ICON: &str = " ";
+
+const DEFAULT_EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", " "), ("md", " "), ("toml", " "), ("py", " "), ("pyi", " "), ("ipynb", " "),
+    ("js", " "), ("mjs", " "), ("cjs", " "), ("ts", " "), ("jsx", " "), ("tsx", " "),
+    ("go", " "), ("c", " "), ("h", " "), ("cpp", " "), ("cc", " "), ("cxx", " "),
+    ("hpp", " "), ("java", " "), ("jar", " "), ("rb", " "), ("php", " "), ("lua", " "),
+    ("html", " "), ("htm", " "), ("css", " "), ("scss", " "), ("json", " "), ("yaml", " "),
+    ("yml", " "), ("xml", " "), ("ini", " "), ("cfg", " "), ("conf", " "), ("png", " "),
+    ("jpg", " "), ("jpeg", " "), ("gif", " "), ("bmp", " "), ("ico", " "), ("webp", " "),
+    ("svg", " "), ("zip", " "), ("tar", " "), ("gz", " "), ("tgz", " "), ("xz", " "),
+    ("bz2", " "), ("7z", " "), ("rar", " "), ("pdf", " "), ("txt", " "), ("log", " "),
+    ("sql", " "), ("db", " "), ("sqlite", " "), ("sh", " "), ("bash", " "), ("zsh", " "),
+    ("fish", " "), ("ps1", " "), ("bat", " "), ("lock", " "), ("vim", " "),
+];
+const DEFAULT_FILENAMES: &[(&str, &str)] = &[
+    ("Cargo.lock", " "), ("package.json", " "),
+    ("package-lock.json", " "), ("Dockerfile", " "), ("docker-compose.yml", " "),
+    ("Makefile", " "), ("LICENSE", " "),
+];
+const DEFAULT_DIR_NAMES: &[(&str, &str)] = &[
+    (".git", " "), (".github", " "), ("node_modules", " "),
+    ("src", " "), (".config", " "), (".vscode", " "),
+];
+
+// Icons keyed by file name and by lowercase extension. User entries from the `[icons.extensions]`
+// and `[icons.filenames]` tables of the config file are layered over the built-in defaults.
+#[derive(Debug, Clone)]
+pub struct IconMap {
+    extensions: HashMap<String, String>,
+    filenames: HashMap<String, String>,
+}
+
+impl Default for IconMap {
+    fn default() -> Self {
+        let owned = |table: &[(&str, &str)]| table.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        Self { extensions: owned(DEFAULT_EXTENSIONS), filenames: owned(DEFAULT_FILENAMES) }
+    }
+}
+
+impl IconMap {
+    pub fn from_config(config: &Config) -> Self {
+        let mut icons = Self::default();
+        for (table, map, lowercase) in [(&config.icons.extensions, &mut icons.extensions, true), (&config.icons.filenames, &mut icons.filenames, false)] {
+            for (key, value) in table {
+                let Value::String(glyph) = value else {
+                    eprintln!("Warning: ignoring icon for `{}`: expected a string", key);
+                    continue;
+                };
+                let key = if lowercase { key.trim_start_matches('.').to_lowercase() } else { key.clone() };
+                map.insert(key, format!("{} ", glyph));
+            }
+        }
+        icons
+    }
+
+    pub fn dir_icon(&self, dir_name: &str) -> &str {
+        DEFAULT_DIR_NAMES.iter().find(|(name, _)| *name == dir_name).map_or(DIR_ICON, |(_, icon)| icon)
+    }
+
+    pub fn file_icon(&self, file_name: &str) -> &str {
+        if let Some(icon) = self.filenames.get(file_name) { return icon; }
+        let ext = Path::new(file_name).extension().map(|e| e.to_string_lossy().to_lowercase());
+        if let Some(icon) = ext.and_then(|e| self.extensions.get(&e)) { return icon; }
+        if file_name.starts_with(".git") { GIT_ICON } else { DEFAULT_FILE_ICON }
+    }
+}
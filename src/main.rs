@@ -1,278 +1,1878 @@
+mod config;
 mod git;
+mod icons;
 mod platform;
+mod watch;
 
-use crate::git::GitStatusCache;
+use crate::config::Config;
+use crate::git::{GitStatusCache, StatusPair};
+use crate::icons::{IconMap, GIT_ICON};
+use crate::watch::ChangeWatcher;
 use chrono::{DateTime, Local};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use globset::{Glob, GlobMatcher};
 use crossterm::{
-    execute,
-    style::{Color, Print, ResetColor, SetForegroundColor, Stylize},
+    cursor::MoveTo,
+    queue, Command,
+    style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor, Stylize},
+    terminal::{Clear, ClearType},
+    tty::IsTty,
 };
-use humansize::{format_size, DECIMAL};
-use ignore::WalkBuilder;
-use std::io::{stdout, Result};
+use humansize::{format_size, BINARY, DECIMAL};
+use ignore::{WalkBuilder, WalkState};
+use std::io::{stdout, BufWriter, Result, Write};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[derive(Parser, Debug, Clone)]
+#[command(author, version, about, long_about = None, args_override_self = true)]
+#[command(group(clap::ArgGroup::new("descent").args(["tree", "recursive", "du"])))]
+// -n turns on the long view, so whatever needs or rules out --long does the same for it.
+#[command(group(clap::ArgGroup::new("long_view").multiple(true).args(["long", "numeric_uid_gid"])))]
 struct Args {
+    /// The files and directories to list; directories are listed after the files, each under its own header if there are several
     #[arg(default_value = ".")]
-    path: PathBuf,
+    paths: Vec<PathBuf>,
     #[arg(short, long)]
     long: bool,
     #[arg(short, long)]
     tree: bool,
-    #[arg(long, default_value_t = usize::MAX, requires = "tree")]
+    /// In the tree view, merge chains of directories that each hold a single subdirectory into one `a/b/c` entry
+    #[arg(long, requires = "tree")]
+    collapse: bool,
+    /// In the tree view, leave out directories with no listed files anywhere below them
+    #[arg(long, requires = "tree")]
+    prune: bool,
+    /// Show each entry by its absolute path instead of its name (flat views only)
+    #[arg(long, conflicts_with = "tree")]
+    full_path: bool,
+    /// Draw the tree view upside down: each directory's entries come before it, and the root comes last
+    #[arg(long, requires = "tree")]
+    tree_reverse: bool,
+    /// In the tree view, show after each directory's name how many entries it holds, counting those beyond --depth too
+    #[arg(long, requires = "tree")]
+    dir_counts: bool,
+    /// Mark directories that are mount points, where another file system starts, with `⊙` (`[mount]` with --ascii)
+    #[arg(long)]
+    mounts: bool,
+    /// Do not enter or size directories on another file system than the one they are listed in, like du -x
+    #[arg(short = 'x', long)]
+    one_file_system: bool,
+    /// Use ASCII instead of box-drawing characters for the tree connectors and the long view's header rule
+    #[arg(long)]
+    ascii: bool,
+    /// Limit how deep the tree, recursive and --du views go: 1 lists only the immediate children, 2 their children too, and so on (at least 1; unlimited by default)
+    #[arg(long, default_value_t = usize::MAX, hide_default_value = true, value_parser = parse_depth,
+        default_value_if("du", clap::builder::ArgPredicate::IsPresent, "1"), requires = "descent")]
     depth: usize,
+    /// Show at most this many entries of each directory (after sorting), followed by a count of the rest (left out by --json, --csv and --markdown)
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Show hidden files and directories and do not respect .gitignore; flat and recursive listings also start with `.` and `..`
     #[arg(short, long)]
     all: bool,
+    /// Like --all, but without the `.` and `..` entries
+    #[arg(short = 'A', long)]
+    almost_all: bool,
+    /// Keep hiding git-ignored entries with --all or --almost-all, which then only add hidden files
+    #[arg(long)]
+    no_ignored: bool,
+    /// Only list entries whose name matches the glob pattern; may be repeated (directories are still traversed in tree view)
+    #[arg(long, value_parser = parse_glob)]
+    glob: Vec<GlobMatcher>,
+    /// Only list entries whose name contains the text, ignoring case, with the match shown in bold (the tree view keeps the directories leading to matches)
+    #[arg(long)]
+    filter: Option<String>,
+    /// Hide entries whose name matches the glob pattern; may be repeated (matching directories are not descended into)
+    #[arg(long, value_parser = parse_glob)]
+    ignore_glob: Vec<GlobMatcher>,
+    /// Hide entries matching the patterns of this gitignore-style file, even with --all; may be repeated. The files rank
+    /// below .gitignore, .git/info/exclude and the global gitignore, so a `!pattern` in those shows the entry again
+    #[arg(long, value_name = "PATH", value_parser = parse_ignore_file)]
+    ignore_file: Vec<PathBuf>,
+    /// Only list files with one of these comma-separated extensions, compared case-insensitively against the
+    /// final extension only (so `gz` matches `a.tar.gz` but `tar.gz` does not)
+    #[arg(long, value_delimiter = ',')]
+    ext: Vec<String>,
+    /// Only list directories
+    #[arg(long)]
+    only_dirs: bool,
+    /// Only list files (not available in tree or recursive views, which need the directories)
+    #[arg(long, conflicts_with_all = ["only_dirs", "tree", "recursive"])]
+    only_files: bool,
+    /// Pack entries into columns even when not writing to a terminal (the default on terminals)
+    #[arg(long, conflicts_with = "long_view")]
+    grid: bool,
+    /// List one entry per line, even on terminals
+    #[arg(short = '1', long, conflicts_with = "grid")]
+    oneline: bool,
+    /// End each entry with a NUL byte instead of a newline and print only its raw name, for `xargs -0`
+    #[arg(short = '0', long, conflicts_with_all = ["long_view", "tree", "recursive", "grid", "sections", "group_by_extension", "json", "csv"])]
+    null: bool,
+    /// Lay the grid out for this many columns instead of the terminal width (0 puts every entry on one line)
+    #[arg(short = 'w', long)]
+    width: Option<usize>,
+    /// Cut names wider than this many columns short with an ellipsis (sorting and JSON/CSV output keep the full names)
+    #[arg(long, value_name = "N")]
+    max_name_width: Option<usize>,
     #[arg(long)]
     git: bool,
-    #[arg(long, requires = "long")]
+    #[arg(long, requires = "long_view")]
     calculate_sizes: bool,
+    /// Show the number of immediate children in the size column of directories (much cheaper than --calculate-sizes)
+    #[arg(long, requires = "long_view", conflicts_with = "calculate_sizes")]
+    count: bool,
+    /// Show each entry's inode number (the file index on Windows) in front of it
+    #[arg(short, long)]
+    inode: bool,
+    /// Show the numeric permission mode (e.g. 0755) before the symbolic one
+    #[arg(long)]
+    octal: bool,
+    /// How to show sizes: `human` in decimal units (kB, MB), `binary` in binary units (KiB, MiB),
+    /// `si` compactly in powers of 1000 (5.1k, 23M), `bytes` as exact counts with thousands separators
+    #[arg(long, value_enum, default_value_t = SizeFormat::Human)]
+    size_format: SizeFormat,
+    /// Show the disk space allocated to files (512-byte blocks × 512) instead of their length, revealing sparse
+    /// files and file system overhead; Windows always shows the length
+    #[arg(long)]
+    blocks: bool,
+    /// When to use colors; `auto` colors only when writing to a terminal and NO_COLOR is unset
+    #[arg(long, value_enum, default_value_t = ColorWhen::Auto)]
+    color: ColorWhen,
+    /// Disable colored output (same as --color=never)
+    #[arg(long)]
+    no_color: bool,
+    /// When to print file icons; `auto` leaves them out on dumb terminals and with a non-UTF-8 locale
+    #[arg(long, value_enum, default_value_t = IconsWhen::Auto)]
+    icons: IconsWhen,
+    /// Do not print file icons (same as --icons=never)
+    #[arg(long)]
+    no_icons: bool,
+    /// Make each name a link to the file (an OSC 8 hyperlink) that supporting terminals open on click; ignored when not writing to a terminal
+    #[arg(long)]
+    hyperlink: bool,
+    /// Do not print the column header in long view
+    #[arg(long)]
+    no_header: bool,
+    /// Decoration-free output for scripts: implies --color=never, --icons=never, --no-header and --quoting-style=literal
+    #[arg(long)]
+    plain: bool,
+    /// How to print names with special characters; by default control characters are shown as `?`
+    #[arg(long, value_enum)]
+    quoting_style: Option<QuotingStyle>,
+    /// Ignore the config file (~/.config/ls-pro/config.toml, or $XDG_CONFIG_HOME/ls-pro/config.toml)
+    #[arg(long)]
+    no_config: bool,
+    #[arg(skip)]
+    icon_map: IconMap,
+    /// Print a summary line with entry counts and total size in long view (per directory with --recursive), and add the total size to the tree view's summary
+    #[arg(long)]
+    total: bool,
+    /// In long view, print the directory's total size above the listing: its files' sizes, plus its subdirectories' with --calculate-sizes
+    #[arg(long, requires = "long_view", conflicts_with = "tree")]
+    header_total: bool,
+    /// Sort entries by the given key (within the --group-dirs grouping), marking the sorted column in the long view header;
+    /// `none` keeps the order the file system returns them in, which depends on the file system, and groups nothing
+    #[arg(long, value_enum, overrides_with_all = ["size_sort", "time_sort"])]
+    sort: Option<SortKey>,
+    /// Sort by size, largest first (smallest first with --reverse), like ls -S
+    #[arg(short = 'S', long, overrides_with_all = ["sort", "time_sort"])]
+    size_sort: bool,
+    /// Sort by modification time, newest first (oldest first with --reverse), like ls -t, whose letter is taken by --tree here
+    #[arg(long, overrides_with_all = ["sort", "size_sort"])]
+    time_sort: bool,
+    /// Which timestamp the long view shows; access times are unreliable on file systems mounted with noatime or relatime
+    #[arg(long, value_enum, default_value_t = TimeField::Modified)]
+    time: TimeField,
+    /// How to display timestamps in the long view
+    #[arg(long, value_enum, default_value_t = TimeStyle::Absolute)]
+    time_style: TimeStyle,
+    /// Custom strftime format for absolute timestamps
+    #[arg(long, default_value = "%d-%m-%Y %H:%M", value_parser = parse_time_format)]
+    time_format: String,
+    /// Reverse the sort order (within the --group-dirs grouping)
+    #[arg(short, long)]
+    reverse: bool,
+    /// Where to list directories relative to files, applied at every level of tree and recursive views
+    #[arg(long, value_enum, default_value_t = GroupDirs::First)]
+    group_dirs: GroupDirs,
+    /// List subdirectories recursively, each under its own path header
+    #[arg(short = 'R', long, conflicts_with = "tree")]
+    recursive: bool,
+    /// Like du: list only the subdirectories, each with the size of everything below it, largest first and
+    /// followed by the directory's total (--depth sets how many levels are listed, 1 by default)
+    #[arg(long, conflicts_with_all = ["long_view", "only_files", "null", "json", "csv", "sections", "group_by_extension"])]
+    du: bool,
+    /// Stop at the first entry that cannot be read; by default it is reported, left out and the exit status is 1
+    #[arg(long)]
+    strict: bool,
+    /// In long view, show the permissions, size and owner of a symlink's target instead of the link itself
+    #[arg(long)]
+    symlink_target_metadata: bool,
+    /// In long view, leave out the group column
+    #[arg(short = 'G', long)]
+    no_group: bool,
+    /// In long view, show the group but not the owner
+    #[arg(long, conflicts_with = "no_group")]
+    group_only: bool,
+    /// Like --long, but with numeric user and group IDs instead of names (raw SIDs on Windows)
+    #[arg(short, long)]
+    numeric_uid_gid: bool,
+    /// Report a symlink's size as its target's; by default it is the link's own, the length of the path it points to
+    #[arg(long)]
+    dereference_size: bool,
+    /// Follow symlinks: list each link as its target, descending into linked directories in tree and recursive views
+    #[arg(short = 'L', long)]
+    dereference: bool,
+    /// Follow symlinks given as operands but not those found inside directories; without it, operands are only followed when neither --long nor --classify is given
+    #[arg(short = 'H', long)]
+    dereference_command_line: bool,
+    /// Append a type indicator to names: `*` for executables, `@` for symlinks, `|` for FIFOs and `=` for sockets
+    #[arg(short = 'F', long)]
+    classify: bool,
+    /// Print directories and files as two separate sections, each under its own heading
+    #[arg(long, conflicts_with = "tree")]
+    sections: bool,
+    /// Print the entries grouped by extension, each group under a `── ext ──` heading, with directories and extensionless files in groups of their own
+    #[arg(long, conflicts_with_all = ["tree", "sections"])]
+    group_by_extension: bool,
+    /// Print the entries as a JSON array instead of a listing (nested `children` arrays in tree and recursive views)
+    #[arg(long)]
+    json: bool,
+    /// Print the entries as CSV rows under a header line instead of a listing (flat and recursive views only)
+    #[arg(long, conflicts_with_all = ["tree", "json"])]
+    csv: bool,
+    /// Print the entries as a GitHub-flavored Markdown table of name, size and modification time (flat views only)
+    #[arg(long, conflicts_with_all = ["tree", "recursive", "du", "json", "csv", "null", "sections", "group_by_extension"])]
+    markdown: bool,
+    /// Print a detailed breakdown of each path given, one `key: value` line per attribute, instead of a listing
+    #[arg(long, conflicts_with_all = ["tree", "recursive", "du", "json", "csv", "markdown", "null", "watch", "live"])]
+    stat: bool,
+    /// Color the size column by magnitude in long views: green below 1 MB, yellow below 1 GB and red above
+    #[arg(long)]
+    size_heatmap: bool,
+    /// Color the time column by age in long views: bright for the last day, dim once older than a month
+    #[arg(long)]
+    age_colors: bool,
+    /// In tree views, list git-ignored directories (already dimmed by --git) without descending into them
+    #[arg(long, requires = "git")]
+    skip_ignored: bool,
+    /// Show the output through $PAGER (`less -R` if unset) when writing to a terminal
+    #[arg(long)]
+    paginate: bool,
+    /// Redraw the listing every few seconds (2 unless given as --watch=SECONDS) until interrupted
+    #[arg(long, value_name = "SECONDS", num_args = 0..=1, require_equals = true, default_missing_value = "2",
+        value_parser = parse_interval, conflicts_with_all = ["paginate", "null"])]
+    watch: Option<std::time::Duration>,
+    /// Redraw the listing whenever what it shows changes, until interrupted (told by the file system where it can, checked a few times a second otherwise)
+    #[arg(long, conflicts_with_all = ["paginate", "null", "watch"])]
+    live: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum SortKey { Name, Size, Time, Created, Ext, #[value(alias = "natural")] Version, None }
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ColorWhen { Auto, Always, Never }
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum IconsWhen { Auto, Always, Never }
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum TimeStyle { Absolute, Relative }
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum TimeField { Modified, Created, Accessed }
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum GroupDirs { First, Last, None }
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum SizeFormat { Human, Binary, Si, Bytes }
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum QuotingStyle { Literal, Shell, Escape }
+
+impl Args {
+    fn use_color(&self) -> bool { self.color == ColorWhen::Always }
+    fn use_icons(&self) -> bool { self.icons == IconsWhen::Always }
+    fn shows_hidden(&self) -> bool { self.all || self.almost_all }
+    fn follows_links(&self) -> bool { self.symlink_target_metadata || self.dereference }
+
+    // Like ls, a symlink operand is looked at as its target unless the listing is about the links themselves.
+    fn follows_operand_links(&self) -> bool { self.dereference || self.dereference_command_line || !(self.long || self.classify) }
+
+    fn for_operands(&self) -> Args { Args { dereference: self.follows_operand_links(), ..self.clone() } }
+    fn format_size(&self, bytes: u64) -> String {
+        match self.size_format {
+            SizeFormat::Human => format_size(bytes, DECIMAL),
+            SizeFormat::Binary => format_size(bytes, BINARY),
+            SizeFormat::Si => format_si(bytes),
+            SizeFormat::Bytes => group_thousands(bytes),
+        }
+    }
 }
 
 // Data structures
-struct FileInfo { path: PathBuf, is_dir: bool, display_size: String, modified_time: DateTime<Local> }
+#[derive(Clone)]
+// `name` is what gets displayed: the file name for directory entries, the path as given for operands.
+struct FileInfo { path: PathBuf, name: String, is_dir: bool, size_bytes: u64, display_size: String, modified_time: DateTime<Local>, created_time: Option<DateTime<Local>>, link_target: Option<PathBuf> }
 struct DisplayInfo {
-    permissions: String, owner: String, size: String, time: String, git: String,
-    icon: String, name: String, name_color: Color, is_dir: bool,
+    inode: String, permissions: String, links: String, owner: String, group: String, size: String, time: String, git: String,
+    // Set by --size-heatmap for entries whose size column shows a byte count, and by --age-colors.
+    size_color: Option<Color>, time_color: Option<Color>,
+    icon: String, name: String, suffix: &'static str, name_color: Color, is_dir: bool, target: String,
+    // Byte ranges of `name` matching --filter, shown in bold.
+    highlights: Vec<(usize, usize)>,
+    // The `file://` URL the name links to with --hyperlink.
+    url: Option<String>,
 }
-struct TreeNode { info: DisplayInfo, children: Vec<TreeNode> }
+// `merged` counts the directories folded into this one by --collapse, `omitted` the children left out by --limit,
+// and `count` all of a directory's children for --dir-counts.
+struct TreeNode { info: DisplayInfo, children: Vec<TreeNode>, merged: usize, omitted: usize, count: Option<usize> }
+// A directory's entries as the tree view shows them. `bytes` is what they add to the directory's size, including
+// the ones left out by --limit: the bottom-up totals of subdirectories, the length of regular files, and nothing for symlinks.
+struct TreeLevel { nodes: Vec<TreeNode>, omitted: usize, bytes: u64 }
+#[derive(Default)]
+struct ColumnWidths { inode: usize, permissions: usize, links: usize, owner: usize, group: usize, size: usize, time: usize, git: usize }
 #[derive(Default)]
-struct ColumnWidths { owner: usize, size: usize }
+struct Totals { dirs: usize, files: usize, size: u64 }
+
+// Color commands that turn into no-ops when color output is disabled.
+struct SetColor(Color, bool);
+struct ResetStyle(bool);
+
+impl Command for SetColor {
+    fn write_ansi(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        if self.1 { SetForegroundColor(self.0).write_ansi(f) } else { Ok(()) }
+    }
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> Result<()> {
+        if self.1 { SetForegroundColor(self.0).execute_winapi() } else { Ok(()) }
+    }
+}
+
+impl Command for ResetStyle {
+    fn write_ansi(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        if self.0 { ResetColor.write_ansi(f) } else { Ok(()) }
+    }
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> Result<()> {
+        if self.0 { ResetColor.execute_winapi() } else { Ok(()) }
+    }
+}
+
+// A directory's --dir-counts figure, dimmed after its name.
+struct PrintCount(Option<usize>, bool);
+
+impl Command for PrintCount {
+    fn write_ansi(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        let Some(count) = self.0 else { return Ok(()) };
+        SetColor(Color::DarkGrey, self.1).write_ansi(f)?;
+        write!(f, " ({})", count)?;
+        ResetStyle(self.1).write_ansi(f)
+    }
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> Result<()> {
+        panic!("PrintCount is only written as ANSI")
+    }
+    #[cfg(windows)]
+    fn is_ansi_code_supported(&self) -> bool { true }
+}
+
+// An entry's name and suffix, with the --filter matches in bold inside whatever color is already set.
+struct PrintName<'a>(&'a DisplayInfo);
+
+impl Command for PrintName<'_> {
+    fn write_ansi(&self, f: &mut impl std::fmt::Write) -> std::fmt::Result {
+        if let Some(url) = &self.0.url { write!(f, "\x1b]8;;{}\x1b\\", url)?; }
+        let name = &self.0.name;
+        let mut shown = 0;
+        for &(start, end) in &self.0.highlights {
+            f.write_str(&name[shown..start])?;
+            SetAttribute(Attribute::Bold).write_ansi(f)?;
+            f.write_str(&name[start..end])?;
+            SetAttribute(Attribute::NormalIntensity).write_ansi(f)?;
+            shown = end;
+        }
+        f.write_str(&name[shown..])?;
+        if self.0.url.is_some() { f.write_str("\x1b]8;;\x1b\\")?; }
+        f.write_str(self.0.suffix)
+    }
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> Result<()> {
+        panic!("PrintName is only written as ANSI")
+    }
+    #[cfg(windows)]
+    fn is_ansi_code_supported(&self) -> bool { true }
+}
 
 // --- MAIN LOGIC ---
 
 fn main() -> Result<()> {
-    let args = Args::parse();
-    let git_cache = if args.git {
-        GitStatusCache::new(&args.path).unwrap_or_else(|e| {
-            eprintln!("Error creating git cache: {}", e); None
-        })
-    } else { None };
+    let mut args = parse_args();
+    if args.plain {
+        args.color = ColorWhen::Never;
+        args.icons = IconsWhen::Never;
+        args.no_header = true;
+        args.quoting_style.get_or_insert(QuotingStyle::Literal);
+    }
+    if args.no_color { args.color = ColorWhen::Never; }
+    if args.no_icons { args.icons = IconsWhen::Never; }
+    if args.numeric_uid_gid { args.long = true; }
+    // The shortcuts put the biggest and newest entries first, which is --sort's order reversed.
+    if args.size_sort || args.time_sort {
+        args.sort = Some(if args.size_sort { SortKey::Size } else { SortKey::Time });
+        args.reverse = !args.reverse;
+    }
+    if args.icons == IconsWhen::Auto {
+        args.icons = if terminal_supports_icons() { IconsWhen::Always } else { IconsWhen::Never };
+    }
+    if !stdout().is_tty() { args.hyperlink = false; }
+    if args.color == ColorWhen::Auto {
+        args.color = if stdout_supports_color() { ColorWhen::Always } else { ColorWhen::Never };
+    }
+    crossterm::style::force_color_output(args.use_color());
+    if let Some(interval) = args.watch {
+        return watch(&args, interval).or_else(|e| if e.kind() == std::io::ErrorKind::BrokenPipe { Ok(()) } else { Err(e) });
+    }
+    if args.live { return live(&args).or_else(|e| if e.kind() == std::io::ErrorKind::BrokenPipe { Ok(()) } else { Err(e) }); }
 
-    if args.tree {
-        print_tree_view(&args, &git_cache)?;
-    } else {
-        let files = get_entries(&args.path, args.all, args.calculate_sizes)?;
-        if args.long {
-            print_long_view(&files, &git_cache)?;
+    if args.stat {
+        let mut out = BufWriter::new(stdout());
+        let failed = print_stats(&args, &mut out).and_then(|failed| out.flush().map(|_| failed));
+        match failed {
+            Ok(true) => std::process::exit(2),
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => return Err(e),
+            _ => return Ok(()),
+        }
+    }
+
+    let (files, dirs, failed) = resolve_operands(&args)?;
+    let mut pager = if args.paginate && stdout().is_tty() { spawn_pager() } else { None };
+    let mut out: Box<dyn Write> = match pager.as_mut().and_then(|child| child.stdin.take()) {
+        Some(stdin) => Box::new(BufWriter::new(stdin)),
+        None => Box::new(BufWriter::new(stdout())),
+    };
+    let result = print_operands(&files, &dirs, &args, &mut out).and_then(|_| out.flush());
+    drop(out);
+    if let Some(mut child) = pager { child.wait()?; }
+    // Quitting the pager early closes the pipe, which is not an error.
+    if let Err(e) = result && e.kind() != std::io::ErrorKind::BrokenPipe { return Err(e); }
+    let unreadable = UNREADABLE.load(Ordering::Relaxed);
+    if unreadable > 0 { eprintln!("ls-pro: {} {} could not be read", unreadable, if unreadable == 1 { "entry" } else { "entries" }); }
+    if failed { std::process::exit(2); }
+    if unreadable > 0 { std::process::exit(1); }
+    Ok(())
+}
+
+// Like ls, file operands are listed together first, then each directory on its own. The flag tells whether
+// any operand could not be accessed.
+fn resolve_operands(args: &Args) -> Result<(Vec<FileInfo>, Vec<PathBuf>, bool)> {
+    let (mut files, mut dirs, mut failed) = (Vec::new(), Vec::new(), false);
+    let operand_args = args.for_operands();
+    for path in &args.paths {
+        match path.symlink_metadata() {
+            Ok(metadata) if resolves_to_dir(path, &metadata, &operand_args) => dirs.push(path.clone()),
+            Ok(metadata) => files.push(FileInfo { name: path.to_string_lossy().into_owned(), ..file_info(path.clone(), metadata, &operand_args, false)? }),
+            Err(e) => { eprintln!("ls-pro: cannot access '{}': {}", path.display(), e); failed = true; }
+        }
+    }
+    files.sort_by(|a, b| order_entries(a, b, &operand_args));
+    Ok((files, dirs, failed))
+}
+
+fn watch(args: &Args, interval: std::time::Duration) -> Result<()> {
+    loop {
+        draw_frame(&format!("Every {}s", interval.as_secs_f64()), args)?;
+        std::thread::sleep(interval);
+    }
+}
+
+// The listed directories are watched for changes, and a redraw follows once a burst of them has settled and
+// only if the listing could look different. The watcher is set up again before each check so that directories
+// created in the meantime are watched too. Without one, the files are checked a few times a second, and a
+// state is only drawn once two checks in a row agree on it.
+fn live(args: &Args) -> Result<()> {
+    let mut drawn = None;
+    loop {
+        let watcher = ChangeWatcher::new(&watched_dirs(args));
+        let current = Some(listing_fingerprint(args));
+        if current != drawn {
+            draw_frame("Live", args)?;
+            drawn = current;
+        }
+        match watcher {
+            Some(watcher) => watcher.wait(),
+            None => {
+                let mut last = current;
+                loop {
+                    std::thread::sleep(std::time::Duration::from_millis(250));
+                    let now = Some(listing_fingerprint(args));
+                    if now == last && now != drawn { break; }
+                    last = now;
+                }
+            }
+        }
+    }
+}
+
+// How deep a change can make the listing look different: all the way down when sizes are summed.
+fn live_depth(args: &Args) -> usize {
+    if args.calculate_sizes || args.du { usize::MAX } else if args.tree || args.recursive { args.depth } else { 1 }
+}
+
+// The directories whose entries the listing shows, the parents of file operands, and with --git the repository
+// directory holding the index and HEAD.
+fn watched_dirs(args: &Args) -> Vec<PathBuf> {
+    let depth = live_depth(args);
+    let mut dirs = Vec::new();
+    for path in &args.paths {
+        if path.is_dir() {
+            // Watching a directory covers its entries, so the deepest listed level needs no watches of its own.
+            dirs.extend(walk_builder(path, args).max_depth(Some(depth - 1)).build().flatten()
+                .filter(|entry| entry.file_type().is_some_and(|t| t.is_dir())).map(|entry| entry.into_path()));
         } else {
-            print_simple_view(&files, &git_cache)?;
+            dirs.push(path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new(".")).to_path_buf());
+        }
+        if args.git { dirs.extend(git::state_files(path).iter().filter_map(|file| file.parent().map(Path::to_path_buf))); }
+    }
+    dirs.sort();
+    dirs.dedup();
+    dirs
+}
+
+// Each frame is rendered before the screen is cleared, so the old listing stays up while the new one is built.
+// The terminal width and git statuses are looked up again on every frame.
+fn draw_frame(label: &str, args: &Args) -> Result<()> {
+    let mut frame = Vec::new();
+    let color = args.use_color();
+    queue!(frame, SetColor(Color::DarkGrey, color),
+        Print(format!("{}, last at {}\n\n", label, Local::now().format("%H:%M:%S"))), ResetStyle(color))?;
+    if let Err(e) = resolve_operands(args).and_then(|(files, dirs, _)| print_operands(&files, &dirs, args, &mut frame)) {
+        writeln!(frame, "ls-pro: {}", e)?;
+    }
+    let mut stdout = stdout();
+    queue!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+    stdout.write_all(&frame)?;
+    stdout.flush()
+}
+
+// Covers the path, size and modification time of everything the listing could show or sum up, and with --git the
+// repository files that record staging and the checked-out branch.
+fn listing_fingerprint(args: &Args) -> u64 {
+    use std::hash::{Hash, Hasher};
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for path in &args.paths {
+        for entry in walk_builder(path, args).max_depth(Some(live_depth(args))).build().flatten() {
+            entry.path().hash(&mut hasher);
+            if let Ok(metadata) = entry.metadata() { (metadata.len(), metadata.modified().ok()).hash(&mut hasher); }
+        }
+        if args.git {
+            for file in git::state_files(path) { std::fs::metadata(file).and_then(|md| md.modified()).ok().hash(&mut hasher); }
+        }
+    }
+    hasher.finish()
+}
+
+fn print_operands(files: &[FileInfo], dirs: &[PathBuf], args: &Args, out: &mut impl Write) -> Result<()> {
+    // A file looked up on the previous frame of --watch or --live may have changed since.
+    platform::forget_file_information();
+    if args.json { return print_json(files, dirs, args, out); }
+    if args.csv { return print_csv(files, dirs, args, out); }
+    if args.markdown { return print_markdown(files, dirs, args, out); }
+    let operand_args = args.for_operands();
+    if !files.is_empty() { print_listing(files, &operand_args, &if args.null { None } else { operand_git_cache(files, args) }, out)?; }
+    for (i, dir) in dirs.iter().enumerate() {
+        if (i > 0 || !files.is_empty()) && !args.null { writeln!(out)?; }
+        if args.paths.len() > 1 && !args.tree && !args.null { writeln!(out, "{}:", dir.display())?; }
+        let git_cache = if args.null { None } else { build_git_cache(dir, args) };
+        if let Some(cache) = &git_cache { print_branch_header(cache, args, out)?; }
+        if args.tree {
+            print_tree_view(dir, args, &git_cache, out)?;
+        } else if args.recursive {
+            print_recursive_view(dir, 0, args, &git_cache, out)?;
+        } else if args.du {
+            print_du(dir, args, out)?;
+        } else {
+            let entries = with_dot_entries(dir, get_entries(dir, args)?, args)?;
+            if args.header_total { print_header_total(&entries, args, out)?; }
+            let (shown, omitted) = limit_entries(&entries, args);
+            print_listing(shown, args, &git_cache, out)?;
+            print_more("", omitted, args, out)?;
         }
     }
     Ok(())
 }
 
+// Unless the user configured less themselves, it is told to pass the color escapes through.
+fn spawn_pager() -> Option<std::process::Child> {
+    let pager = std::env::var("PAGER").ok().filter(|p| !p.trim().is_empty()).unwrap_or_else(|| "less -R".to_string());
+    let mut words = pager.split_whitespace();
+    let mut command = std::process::Command::new(words.next()?);
+    command.args(words).stdin(std::process::Stdio::piped());
+    if std::env::var_os("LESS").is_none() { command.env("LESS", "R"); }
+    command.spawn().ok()
+}
+
+fn build_git_cache(path: &Path, args: &Args) -> Option<GitStatusCache> {
+    if !args.git { return None; }
+    GitStatusCache::new(path).unwrap_or_else(|e| {
+        eprintln!("Error creating git cache: {}", e); None
+    })
+}
+
+// File operands share the repository of the first one's directory.
+fn operand_git_cache(files: &[FileInfo], args: &Args) -> Option<GitStatusCache> {
+    let dir = files.first()?.path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    build_git_cache(dir, args)
+}
+
+// Config file values are passed to clap ahead of the real command line, so flags given on the
+// command line override them. Each value is tried on top of the ones kept so far: one that conflicts
+// with the command line gives way to it, one that needs a flag nobody gave (like `depth` without a
+// view that descends) sits out, and any other failure is reported and skips only that value.
+fn parse_args() -> Args {
+    let args = Args::parse();
+    if args.no_config { return args; }
+    let Some(config) = Config::load() else { return args };
+    let mut cli = std::env::args_os();
+    let program = cli.next().unwrap_or_default();
+    let cli: Vec<_> = cli.collect();
+    let parse = |config_args: &[std::ffi::OsString], cli: &[std::ffi::OsString]| {
+        Args::try_parse_from(std::iter::once(&program).chain(config_args).chain(cli))
+    };
+    let (mut kept, mut waiting) = (Vec::new(), Vec::new());
+    // Values waiting on a flag get a second try once the rest are in, since it may come later in the file.
+    for second_try in [false, true] {
+        let values = if second_try { std::mem::take(&mut waiting) } else { config.to_args() };
+        for value in values {
+            let tried: Vec<_> = kept.iter().cloned().chain([value.clone()]).collect();
+            match parse(&tried, &cli) {
+                Ok(_) => kept.push(value),
+                Err(e) if e.kind() == clap::error::ErrorKind::MissingRequiredArgument => if !second_try { waiting.push(value) },
+                Err(e) if e.kind() == clap::error::ErrorKind::ArgumentConflict && parse(&tried, &[]).is_ok() => {}
+                Err(e) => eprintln!("Warning: ignoring `{}` from the config file: {}", value.to_string_lossy(), e.to_string().trim_end()),
+            }
+        }
+    }
+    let mut args = parse(&kept, &cli).unwrap_or(args);
+    args.icon_map = IconMap::from_config(&config);
+    args
+}
+
+// Colors are only emitted to a terminal, and never when NO_COLOR is set (https://no-color.org).
+fn stdout_supports_color() -> bool {
+    std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && stdout().is_tty()
+}
+
+// The Nerd Font glyphs need a UTF-8 locale; an unset locale is assumed to be fine, as on Windows.
+fn terminal_supports_icons() -> bool {
+    if std::env::var_os("TERM").is_some_and(|term| term == "dumb") { return false; }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter().find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()));
+    locale.is_none_or(|locale| { let locale = locale.to_lowercase(); locale.contains("utf-8") || locale.contains("utf8") })
+}
+
+fn parse_interval(seconds: &str) -> std::result::Result<std::time::Duration, String> {
+    match seconds.parse::<f64>() {
+        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Ok(std::time::Duration::from_secs_f64(seconds)),
+        _ => Err("expected a positive number of seconds".to_string()),
+    }
+}
+
+// A depth of 0 would list nothing below the directory in the tree view, so it is refused for every view alike.
+fn parse_depth(depth: &str) -> std::result::Result<usize, String> {
+    match depth.parse::<usize>() {
+        Ok(depth) if depth > 0 => Ok(depth),
+        _ => Err("expected a whole number of levels, at least 1".to_string()),
+    }
+}
+
+fn parse_time_format(format: &str) -> std::result::Result<String, String> {
+    use std::fmt::Write;
+    let mut sample = String::new();
+    match write!(sample, "{}", Local::now().format(format)) {
+        Ok(()) if !sample.is_empty() => Ok(format.to_string()),
+        _ => Err(format!("'{}' is not a valid strftime format", format)),
+    }
+}
+
+fn parse_glob(pattern: &str) -> std::result::Result<GlobMatcher, String> {
+    Glob::new(pattern).map(|glob| glob.compile_matcher()).map_err(|e| e.to_string())
+}
+
+// Read once here so an unreadable file or a bad pattern is reported up front instead of by every walk.
+fn parse_ignore_file(path: &str) -> std::result::Result<PathBuf, String> {
+    std::fs::File::open(path).map_err(|e| e.to_string())?;
+    match ignore::gitignore::Gitignore::new(path) {
+        (_, Some(e)) => Err(e.to_string()),
+        (_, None) => Ok(PathBuf::from(path)),
+    }
+}
+
+// Like `ls --si`: one decimal below 10 of a unit, whole numbers above.
+fn format_si(bytes: u64) -> String {
+    const UNITS: [char; 6] = ['k', 'M', 'G', 'T', 'P', 'E'];
+    let (mut value, mut unit) = (bytes as f64, 0);
+    while value >= 1000.0 && unit < UNITS.len() { value /= 1000.0; unit += 1; }
+    if unit == 0 { bytes.to_string() }
+    else if value < 10.0 { format!("{:.1}{}", value, UNITS[unit - 1]) }
+    else { format!("{:.0}{}", value, UNITS[unit - 1]) }
+}
+
+fn group_thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) { grouped.push(','); }
+        grouped.push(c);
+    }
+    grouped
+}
+
+fn print_branch_header(cache: &GitStatusCache, args: &Args, out: &mut impl Write) -> Result<()> {
+    let Some((branch, ahead, behind)) = cache.branch_info() else { return Ok(()) };
+    let color = args.use_color();
+    let mut counts = String::new();
+    if ahead > 0 { counts.push_str(&format!(" ↑{}", ahead)); }
+    if behind > 0 { counts.push_str(&format!(" ↓{}", behind)); }
+    queue!(out, Print(if args.use_icons() { GIT_ICON } else { "" }), SetColor(Color::Magenta, color), Print(branch), ResetStyle(color),
+        SetColor(Color::Yellow, color), Print(format!("{}\n", counts)), ResetStyle(color))
+}
+
 // --- DATA GATHERING FUNCTIONS ---
 
-fn calculate_dir_size(path: &Path, show_hidden: bool) -> u64 {
-    WalkBuilder::new(path).hidden(!show_hidden).git_ignore(!show_hidden).build()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().map_or(false, |ft| ft.is_file()))
-        .filter_map(|e| e.metadata().ok()).map(|md| md.len()).sum()
+// Every walk applies the same visibility rules, so counts and sizes cover exactly what a listing would show.
+// The tree and recursive views start a walk in each directory; WalkBuilder also reads the ignore files of the
+// directories above it up to the repository root, so their patterns, anchored ones included, apply there too.
+fn walk_builder(path: &Path, args: &Args) -> WalkBuilder {
+    let mut builder = WalkBuilder::new(path);
+    let git_rules = !args.shows_hidden() || args.no_ignored;
+    builder.hidden(!args.shows_hidden()).git_ignore(git_rules).git_exclude(git_rules).git_global(git_rules)
+        .same_file_system(args.one_file_system);
+    for file in &args.ignore_file { builder.add_ignore(file); }
+    builder
+}
+
+fn file_size(metadata: &std::fs::Metadata, blocks: bool) -> u64 {
+    if blocks { platform::allocated_size(metadata).unwrap_or(metadata.len()) } else { metadata.len() }
+}
+
+fn calculate_dir_size(path: &Path, args: &Args) -> u64 {
+    let total = AtomicU64::new(0);
+    let blocks = args.blocks;
+    walk_builder(path, args).build_parallel().run(|| {
+        let total = &total;
+        Box::new(move |result| {
+            if let Ok(entry) = result
+                && entry.file_type().is_some_and(|ft| ft.is_file())
+                && let Ok(md) = entry.metadata() {
+                total.fetch_add(file_size(&md, blocks), Ordering::Relaxed);
+            }
+            WalkState::Continue
+        })
+    });
+    total.into_inner()
 }
 
-fn get_entries(path: &Path, show_hidden: bool, calc_sizes: bool) -> Result<Vec<FileInfo>> {
+// What an entry adds to a total. Like the walk above, a symlink adds nothing, whatever it points to.
+fn counted_size(file: &FileInfo) -> u64 {
+    if file.link_target.is_some() { 0 } else { file.size_bytes }
+}
+
+fn count_children(path: &Path, args: &Args) -> usize {
+    walk_builder(path, args).max_depth(Some(1)).build()
+        .filter_map(|e| e.ok()).filter(|e| e.depth() > 0).count()
+}
+
+fn get_entries(path: &Path, args: &Args) -> Result<Vec<FileInfo>> {
+    let mut entries = read_entries(path, args, args.calculate_sizes)?;
+    if args.sort != Some(SortKey::None) { entries.sort_by(|a, b| order_entries(a, b, args)); }
+    Ok(entries)
+}
+
+// With --all, `.` and `..` stand for the directory itself and its parent, ahead of the sorted entries. They are
+// never sized, since `..` could mean walking far more than the listing, and stay out of the totals.
+// Listings narrowed down by name or type leave them out.
+fn with_dot_entries(path: &Path, mut entries: Vec<FileInfo>, args: &Args) -> Result<Vec<FileInfo>> {
+    if !args.all || args.only_files || has_entry_filters(args) { return Ok(entries); }
+    let mut dots = Vec::new();
+    for name in [".", ".."] {
+        let dot = path.join(name);
+        dots.push(FileInfo { name: name.to_string(), display_size: "-".to_string(), ..file_info(dot.clone(), dot.symlink_metadata()?, args, false)? });
+    }
+    entries.splice(0..0, dots);
+    Ok(entries)
+}
+
+fn is_dot_entry(file: &FileInfo) -> bool { file.name == "." || file.name == ".." }
+
+fn read_entries(path: &Path, args: &Args, walk_dir_sizes: bool) -> Result<Vec<FileInfo>> {
     let mut entries = Vec::new();
-    let walk = WalkBuilder::new(path).hidden(!show_hidden).git_ignore(!show_hidden).max_depth(Some(1)).build();
-    for result in walk {
-        if let Ok(entry) = result {
-            if entry.depth() == 0 { continue; }
-            if let Ok(metadata) = entry.metadata() {
-                let path = entry.into_path();
-                let is_dir = metadata.is_dir();
-                let display_size = if is_dir {
-                    if calc_sizes { format_size(calculate_dir_size(&path, show_hidden), DECIMAL) } 
-                    else { "-".to_string() }
-                } else { format_size(metadata.len(), DECIMAL) };
-                entries.push(FileInfo { path, is_dir, display_size, modified_time: metadata.modified()?.into() });
+    let walk = walk_builder(path, args).max_depth(Some(1)).build();
+    for entry in walk {
+        // Only the directory itself is read, so that is what a walk error is about.
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                let e = walk_io_error(&e);
+                let e = std::io::Error::new(e.kind(), format!("cannot open directory '{}': {}", path.display(), e));
+                if args.strict { return Err(e); }
+                report_unreadable(&e.to_string());
+                continue;
             }
+        };
+        if entry.depth() == 0 { continue; }
+        let Some(metadata) = skip_unreadable(entry.metadata().map_err(|e| walk_io_error(&e)), entry.path(), args)? else { continue };
+        if is_listed(entry.path(), resolves_to_dir(entry.path(), &metadata, args), args) {
+            let path = entry.into_path();
+            if let Some(file) = skip_unreadable(file_info(path.clone(), metadata, args, walk_dir_sizes), &path, args)? { entries.push(file); }
         }
     }
-    entries.sort_by(|a, b| {
-        if a.is_dir && !b.is_dir { std::cmp::Ordering::Less }
-        else if !a.is_dir && b.is_dir { std::cmp::Ordering::Greater }
-        else { a.path.file_name().cmp(&b.path.file_name()) }
-    });
     Ok(entries)
 }
 
-fn build_tree_nodes(path: &Path, depth: usize, max_depth: usize, show_hidden: bool, calc_sizes: bool, git_cache: &Option<GitStatusCache>) -> Result<Vec<TreeNode>> {
-    if depth >= max_depth { return Ok(Vec::new()); }
-    let entries = get_entries(path, show_hidden, calc_sizes)?;
+static UNREADABLE: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+// Unless --strict, an entry that cannot be read is reported and left out, and the listing goes on.
+fn skip_unreadable<T>(result: Result<T>, path: &Path, args: &Args) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if args.strict => Err(e),
+        Err(e) => { report_unreadable(&format!("cannot access '{}': {}", path.display(), e)); Ok(None) }
+    }
+}
+
+// The walker wraps OS errors in messages of its own that repeat the path.
+fn walk_io_error(e: &ignore::Error) -> std::io::Error {
+    let mut source = e.io_error().map(|io| io as &(dyn std::error::Error + 'static));
+    while let Some(err) = source {
+        if let Some(code) = err.downcast_ref::<std::io::Error>().and_then(std::io::Error::raw_os_error) { return std::io::Error::from_raw_os_error(code); }
+        source = err.source();
+    }
+    std::io::Error::other(e.to_string())
+}
+
+fn report_unreadable(message: &str) {
+    eprintln!("ls-pro: {}", message);
+    UNREADABLE.fetch_add(1, Ordering::Relaxed);
+}
+
+fn resolves_to_dir(path: &Path, metadata: &std::fs::Metadata, args: &Args) -> bool {
+    if args.dereference && metadata.file_type().is_symlink() { path.is_dir() } else { metadata.is_dir() }
+}
+
+// `metadata` is the entry's own, not following symlinks.
+fn file_info(path: PathBuf, metadata: std::fs::Metadata, args: &Args, walk_dir_sizes: bool) -> Result<FileInfo> {
+    let link_target = if metadata.file_type().is_symlink() { std::fs::read_link(&path).ok() } else { None };
+    let target_metadata = if link_target.is_some() && (args.follows_links() || args.dereference_size) { path.metadata().ok() } else { None };
+    let is_dir = resolves_to_dir(&path, &metadata, args);
+    let name = path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned();
+    let unsized_dir = is_dir && (!args.calculate_sizes || crosses_file_system(&path, args));
+    let size_bytes = if is_dir {
+        if walk_dir_sizes && !unsized_dir { calculate_dir_size(&path, args) } else { 0 }
+    } else { file_size(target_metadata.as_ref().unwrap_or(&metadata), args.blocks) };
+    let metadata = target_metadata.filter(|_| args.follows_links()).unwrap_or(metadata);
+    let display_size = if is_dir && args.count {
+        let children = count_children(&path, args);
+        format!("{} {}", children, if children == 1 { "item" } else { "items" })
+    } else if unsized_dir { "-".to_string() } else { args.format_size(size_bytes) };
+    let created_time = metadata.created().ok().map(DateTime::from);
+    if created_time.is_none() && args.sort == Some(SortKey::Created) {
+        static WARNING: std::sync::Once = std::sync::Once::new();
+        WARNING.call_once(|| eprintln!("Warning: creation times are not available here, sorting by modification time instead"));
+    }
+    Ok(FileInfo { path, name, is_dir, size_bytes, display_size, modified_time: metadata.modified()?.into(), created_time, link_target })
+}
+
+fn order_entries(a: &FileInfo, b: &FileInfo, args: &Args) -> std::cmp::Ordering {
+    let sort = args.sort.unwrap_or(SortKey::Name);
+    if sort == SortKey::None { return std::cmp::Ordering::Equal; }
+    let grouping = match args.group_dirs {
+        GroupDirs::First => b.is_dir.cmp(&a.is_dir),
+        GroupDirs::Last => a.is_dir.cmp(&b.is_dir),
+        GroupDirs::None => std::cmp::Ordering::Equal,
+    };
+    grouping.then_with(|| if args.reverse { compare_entries(b, a, sort) } else { compare_entries(a, b, sort) })
+}
+
+fn is_listed(path: &Path, is_dir: bool, args: &Args) -> bool {
+    let name = path.file_name().unwrap_or_default();
+    if args.ignore_glob.iter().any(|glob| glob.is_match(name)) { return false; }
+    if (args.only_dirs && !is_dir) || (args.only_files && is_dir) { return false; }
+    if !is_dir && !args.ext.is_empty() {
+        let ext = path.extension().unwrap_or_default().to_string_lossy();
+        if !args.ext.iter().any(|wanted| wanted.trim_start_matches('.').eq_ignore_ascii_case(&ext)) { return false; }
+    }
+    let descends = is_dir && (args.tree || args.recursive);
+    descends || ((args.glob.is_empty() || args.glob.iter().any(|glob| glob.is_match(name))) && matches_filter(path, args))
+}
+
+fn matches_filter(path: &Path, args: &Args) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_lowercase();
+    args.filter.as_ref().is_none_or(|filter| name.contains(&filter.to_lowercase()))
+}
+
+// A directory reached through a symlink (with --dereference) is only entered if it is not one of its own
+// ancestors, which would otherwise be listed forever.
+fn descends_into(file: &FileInfo, args: &Args) -> bool {
+    if !file.is_dir || crosses_file_system(&file.path, args) { return false; }
+    if file.link_target.is_none() { return true; }
+    match (file.path.canonicalize(), file.path.parent().and_then(|p| p.canonicalize().ok())) {
+        (Ok(target), Some(parent)) => !parent.starts_with(target),
+        _ => false,
+    }
+}
+
+// With --one-file-system, a directory on another device than the one it is listed in is shown but neither
+// entered nor sized.
+fn crosses_file_system(path: &Path, args: &Args) -> bool {
+    if !args.one_file_system { return false; }
+    let device = |path: &Path| std::fs::metadata(path).ok().and_then(|md| platform::device_id(path, &md));
+    path.parent().filter(|parent| !parent.as_os_str().is_empty()).is_some_and(|parent| device(path) != device(parent))
+}
+
+fn has_entry_filters(args: &Args) -> bool {
+    !args.glob.is_empty() || args.filter.is_some() || !args.ignore_glob.is_empty() || !args.ext.is_empty() || args.only_dirs
+}
+
+fn compare_entries(a: &FileInfo, b: &FileInfo, sort: SortKey) -> std::cmp::Ordering {
+    let by_name = || a.path.file_name().cmp(&b.path.file_name());
+    match sort {
+        SortKey::Name => by_name(),
+        SortKey::Size => a.size_bytes.cmp(&b.size_bytes).then_with(by_name),
+        SortKey::Time => a.modified_time.cmp(&b.modified_time).then_with(by_name),
+        SortKey::Created => a.created_time.unwrap_or(a.modified_time).cmp(&b.created_time.unwrap_or(b.modified_time)).then_with(by_name),
+        SortKey::Ext => a.path.extension().cmp(&b.path.extension()).then_with(by_name),
+        SortKey::Version => natural_cmp(a.name.as_bytes(), b.name.as_bytes()).then_with(by_name),
+        SortKey::None => std::cmp::Ordering::Equal,
+    }
+}
+
+// Runs of digits compare by their numeric value, so `file2` sorts before `file10`; equal values with more
+// leading zeros come after.
+fn natural_cmp(mut a: &[u8], mut b: &[u8]) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let b_len = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let zeros = |digits: &[u8]| digits.iter().take_while(|&&c| c == b'0').count();
+                let (a_num, b_num) = (&a[zeros(&a[..a_len])..a_len], &b[zeros(&b[..b_len])..b_len]);
+                let order = a_num.len().cmp(&b_num.len()).then_with(|| a_num.cmp(b_num)).then_with(|| a_len.cmp(&b_len));
+                if order != Ordering::Equal { return order; }
+                (a, b) = (&a[a_len..], &b[b_len..]);
+            }
+            (Some(x), Some(y)) => {
+                if x != y { return x.cmp(y); }
+                (a, b) = (&a[1..], &b[1..]);
+            }
+        }
+    }
+}
+
+fn build_display_info(file: &FileInfo, args: &Args, git_cache: &Option<GitStatusCache>) -> Result<DisplayInfo> {
+    let (metadata, broken) = entry_metadata(file, args)?;
+    let status = git_status(file, git_cache);
+    let file_name_str = file.path.file_name().unwrap_or(file.name.as_ref()).to_string_lossy();
+    let name = if args.full_path { std::path::absolute(&file.path)?.to_string_lossy().into_owned() } else { file.name.clone() };
+    let name = quote_name(&args.max_name_width.map_or(name.clone(), |max| truncate_name(&name, max)), args);
+    let shown_time = match args.time {
+        TimeField::Modified => Some(file.modified_time),
+        TimeField::Created => file.created_time,
+        TimeField::Accessed => metadata.accessed().ok().map(DateTime::from),
+    };
+    Ok(DisplayInfo {
+        inode: if args.inode { platform::get_inode(&file.path, &metadata).map_or_else(String::new, |ino| ino.to_string()) } else { String::new() },
+        permissions: if args.long { format_permissions(&file.path, &metadata, args) } else { String::new() },
+        links: if args.long { platform::get_link_count(&file.path, &metadata).map_or_else(String::new, |n| n.to_string()) } else { String::new() },
+        owner: if args.group_only { String::new() } else { platform::get_owner(&file.path, &metadata, args.numeric_uid_gid) },
+        group: if args.no_group { String::new() } else { platform::get_group(&file.path, &metadata, args.numeric_uid_gid) },
+        size: file.display_size.clone(),
+        size_color: if args.size_heatmap && args.use_color() && !(file.is_dir && (args.count || file.display_size == "-")) { Some(size_heat(file.size_bytes)) } else { None },
+        time: shown_time.map_or_else(|| "-".to_string(), |time| format_time(&time, args)),
+        time_color: shown_time.filter(|_| args.age_colors && args.use_color()).map(|time| age_color(Local::now().signed_duration_since(time))),
+        git: match status {
+            Some(pair) => pair.iter().map(|&(c, color)| if args.use_color() { c.with(color).to_string() } else { c.to_string() }).collect(),
+            None if git_cache.is_some() => "  ".to_string(),
+            None if args.plain => String::new(),
+            None => " ".to_string(),
+        },
+        icon: get_icon(&file_name_str, file.is_dir, args).to_string(),
+        url: if args.hyperlink { Some(file_url(&std::path::absolute(&file.path)?)) } else { None },
+        highlights: match &args.filter { Some(filter) if args.use_color() => match_ranges(&name, filter), _ => Vec::new() },
+        name,
+        suffix: classify_suffix(file, &metadata, args),
+        // The worktree status is what needs attention first, so it wins over the staged one.
+        name_color: status.and_then(|pair| pair.iter().rev().find(|(c, _)| *c != ' ').map(|&(_, color)| color))
+            .unwrap_or_else(|| color_for_entry(&file.path, &metadata)),
+        is_dir: file.is_dir,
+        target: match &file.link_target {
+            Some(t) if broken || !args.dereference => format!(" -> {}{}", quote_name(&t.to_string_lossy(), args), if broken { " [broken]" } else { "" }),
+            _ => String::new(),
+        },
+    })
+}
+
+// Case-insensitive occurrences of `needle`, as byte ranges into `name` with its original casing.
+fn match_ranges(name: &str, needle: &str) -> Vec<(usize, usize)> {
+    let needle: Vec<char> = needle.chars().flat_map(char::to_lowercase).collect();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while !needle.is_empty() && start < name.len() {
+        let mut wanted = needle.iter();
+        let mut end = None;
+        for (i, c) in name[start..].char_indices() {
+            if !c.to_lowercase().all(|lower| wanted.next() == Some(&lower)) { break; }
+            if wanted.len() == 0 { end = Some(start + i + c.len_utf8()); break; }
+        }
+        match end {
+            Some(end) => { ranges.push((start, end)); start = end; }
+            None => start += name[start..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    ranges
+}
+
+// Bytes outside the unreserved set are percent-encoded, so spaces and non-ASCII names survive the trip.
+fn file_url(path: &Path) -> String {
+    #[cfg(unix)]
+    let path = path.as_os_str().as_encoded_bytes().to_vec();
+    #[cfg(windows)]
+    let path = format!("/{}", path.to_string_lossy().replace('\\', "/")).into_bytes();
+    let mut url = String::from("file://");
+    for byte in path {
+        if byte.is_ascii_alphanumeric() || b"-._~/:".contains(&byte) { url.push(byte as char); } else { url.push_str(&format!("%{:02X}", byte)); }
+    }
+    url
+}
+
+fn truncate_name(name: &str, max: usize) -> String {
+    if name.width() <= max { return name.to_string(); }
+    let mut width = 0;
+    let mut kept: String = name.chars().take_while(|&c| { width += c.width().unwrap_or(0); width < max }).collect();
+    kept.push('\u{2026}');
+    kept
+}
+
+// `{:<width$}` counts characters, which leaves columns holding wide characters one space too wide per character.
+fn pad(s: &str, width: usize, align_right: bool) -> String {
+    let fill = " ".repeat(width.saturating_sub(s.width()));
+    if align_right { fill + s } else { format!("{}{}", s, fill) }
+}
+
+fn quote_name(name: &str, args: &Args) -> String {
+    let hide_control = || name.chars().map(|c| if c.is_control() { '?' } else { c }).collect::<String>();
+    match args.quoting_style {
+        None => hide_control(),
+        Some(QuotingStyle::Literal) => name.to_string(),
+        Some(QuotingStyle::Shell) => {
+            let safe = !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || "-_./+,:@%=".contains(c));
+            if safe { name.to_string() } else { format!("'{}'", hide_control().replace('\'', "'\\''")) }
+        }
+        Some(QuotingStyle::Escape) => name.chars().map(|c| match c {
+            '\\' => "\\\\".to_string(),
+            ' ' => "\\ ".to_string(),
+            '\n' => "\\n".to_string(),
+            '\r' => "\\r".to_string(),
+            '\t' => "\\t".to_string(),
+            c if c.is_control() => format!("\\{:03o}", c as u32),
+            c => c.to_string(),
+        }).collect(),
+    }
+}
+
+// The metadata shown for an entry, and whether it is a symlink whose target is missing.
+fn entry_metadata(file: &FileInfo, args: &Args) -> Result<(std::fs::Metadata, bool)> {
+    let target_metadata = if file.link_target.is_some() { file.path.metadata().ok() } else { None };
+    let broken = file.link_target.is_some() && target_metadata.is_none();
+    Ok((match target_metadata.filter(|_| args.follows_links()) { Some(md) => md, None => file.path.symlink_metadata()? }, broken))
+}
+
+// Directories always end in `/`; --classify adds `*` for executables, `@` for symlinks, `|` for FIFOs and `=` for sockets.
+fn classify_suffix(file: &FileInfo, metadata: &std::fs::Metadata, args: &Args) -> &'static str {
+    if file.is_dir && args.mounts && is_mount_point(file, metadata) { if args.ascii { "/ [mount]" } else { "/ \u{2299}" } }
+    else if file.is_dir { "/" }
+    else if !args.classify { "" }
+    else if metadata.file_type().is_symlink() { "@" }
+    else if platform::is_executable(&file.path, metadata) { "*" }
+    else { platform::special_file_suffix(metadata) }
+}
+
+// A directory where another file system starts lives on a different device than its parent; a symlink's
+// target may do so anywhere, so links are never marked.
+fn is_mount_point(file: &FileInfo, metadata: &std::fs::Metadata) -> bool {
+    if file.link_target.is_some() { return false; }
+    let parent = file.path.join("..");
+    let parent_device = std::fs::metadata(&parent).ok().and_then(|md| platform::device_id(&parent, &md));
+    platform::device_id(&file.path, metadata).is_some_and(|device| parent_device.is_some_and(|parent| parent != device))
+}
+
+fn git_status(file: &FileInfo, git_cache: &Option<GitStatusCache>) -> Option<StatusPair> {
+    let cache = git_cache.as_ref()?;
+    cache.get(&file.path).or_else(|| if file.is_dir { cache.dir_summary(&file.path) } else { None })
+}
+
+// The two status characters as plain text, or None for a clean path.
+fn git_code(file: &FileInfo, git_cache: &Option<GitStatusCache>) -> Option<String> {
+    git_status(file, git_cache).map(|pair| pair.iter().map(|(c, _)| c).collect::<String>()).filter(|code| !code.trim().is_empty())
+}
+
+fn format_time(time: &DateTime<Local>, args: &Args) -> String {
+    match args.time_style {
+        TimeStyle::Absolute => time.format(&args.time_format).to_string(),
+        TimeStyle::Relative => format_time_ago(Local::now().signed_duration_since(time)),
+    }
+}
+
+fn format_time_ago(age: chrono::TimeDelta) -> String {
+    let units = [("year", 365 * 86400), ("month", 30 * 86400), ("day", 86400), ("hour", 3600), ("minute", 60)];
+    let secs = age.num_seconds();
+    if secs < 0 { return "in the future".to_string(); }
+    for (unit, unit_secs) in units {
+        let n = secs / unit_secs;
+        if n > 0 { return format!("{} {}{} ago", n, unit, if n == 1 { "" } else { "s" }); }
+    }
+    "just now".to_string()
+}
+
+const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "tar", "gz", "tgz", "xz", "bz2", "7z", "rar", "zst", "jar", "deb", "rpm"];
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "svg", "tiff"];
+
+fn color_for_entry(path: &Path, metadata: &std::fs::Metadata) -> Color {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    if metadata.is_dir() { Color::Blue }
+    else if metadata.file_type().is_symlink() { if path.exists() { Color::Cyan } else { Color::Red } }
+    else if platform::is_executable(path, metadata) { Color::Green }
+    else if ARCHIVE_EXTENSIONS.contains(&ext.as_str()) { Color::Red }
+    else if IMAGE_EXTENSIONS.contains(&ext.as_str()) { Color::Magenta }
+    else { Color::White }
+}
+
+fn format_permissions(path: &Path, metadata: &std::fs::Metadata, args: &Args) -> String {
+    let mut symbolic = platform::format_permissions(metadata);
+    symbolic.extend(platform::attribute_marker(path, metadata));
+    match platform::format_octal_mode(metadata) {
+        Some(octal) if args.octal => format!("{} {}", octal, symbolic),
+        _ => symbolic,
+    }
+}
+
+fn build_tree_nodes(path: &Path, depth: usize, args: &Args, git_cache: &Option<GitStatusCache>) -> Result<TreeLevel> {
+    if depth >= args.depth { return Ok(TreeLevel { nodes: Vec::new(), omitted: 0, bytes: 0 }); }
+    // Directory sizes are summed from the subtree built below instead of walking it again, unless the
+    // subtree is cut short by --depth or filters hide files that the flat view's size would include.
+    let bottom_up = args.calculate_sizes && depth + 1 < args.depth && !has_entry_filters(args);
+    let mut entries = Vec::new();
+    for mut file in read_entries(path, args, args.calculate_sizes && !bottom_up)? {
+        let skipped = args.skip_ignored && git_cache.as_ref().is_some_and(|cache| file.is_dir && cache.is_ignored(&file.path));
+        let descended = descends_into(&file, args) && depth + 1 < args.depth && !skipped;
+        let children = if descended { build_tree_nodes(&file.path, depth + 1, args, git_cache)? } else { TreeLevel { nodes: Vec::new(), omitted: 0, bytes: 0 } };
+        // Directories cut off by --depth are kept, since nothing is known about what they hold.
+        let prune = args.prune || !matches_filter(&file.path, args);
+        if prune && descended && children.nodes.is_empty() && children.omitted == 0 { continue; }
+        if bottom_up && (descended || skipped) {
+            file.size_bytes = if descended { children.bytes } else { calculate_dir_size(&file.path, args) };
+            file.display_size = args.format_size(file.size_bytes);
+        }
+        let count = if !args.dir_counts || !file.is_dir { None }
+            else if descended { Some(children.nodes.len() + children.omitted) }
+            else { Some(count_children(&file.path, args)) };
+        entries.push((file, children, count));
+    }
+    // Siblings are ordered like a flat listing, --group-dirs included, after the sizes above are known.
+    entries.sort_by(|(a, ..), (b, ..)| order_entries(a, b, args));
+
+    let bytes = entries.iter().map(|(file, ..)| counted_size(file)).sum();
+    let omitted = entries.len() - limit_entries(&entries, args).0.len();
+    entries.truncate(entries.len() - omitted);
     let mut nodes = Vec::new();
-    for file in entries {
-        let metadata = file.path.metadata()?;
-        let (git_char, git_color) = git_cache.as_ref().and_then(|cache| file.path.canonicalize().ok().and_then(|p| cache.get(&p))).unwrap_or((' ', Color::Reset));
-        let file_name_str = file.path.file_name().unwrap().to_string_lossy();
-        let info = DisplayInfo {
-            permissions: platform::format_permissions(&metadata), owner: platform::get_owner(&metadata),
-            size: file.display_size.clone(), time: file.modified_time.format("%d-%m-%Y %H:%M").to_string(),
-            git: format!("{}", git_char.with(git_color)),
-            icon: if file.is_dir { " ".to_string() } else { get_icon_for_file(&file_name_str).to_string() },
-            name: file_name_str.to_string(),
-            name_color: if git_char != ' ' { git_color } else { if file.is_dir { Color::Blue } else { Color::White } },
-            is_dir: file.is_dir,
-        };
-        let children = if file.is_dir { build_tree_nodes(&file.path, depth + 1, max_depth, show_hidden, calc_sizes, git_cache)? } else { Vec::new() };
-        nodes.push(TreeNode { info, children });
+    for (file, children, count) in entries {
+        let Some(info) = skip_unreadable(build_display_info(&file, args, git_cache), &file.path, args)? else { continue };
+        let mut node = TreeNode { info, children: children.nodes, merged: 0, omitted: children.omitted, count };
+        if args.collapse { collapse_chain(&mut node); }
+        nodes.push(node);
     }
-    Ok(nodes)
+    Ok(TreeLevel { nodes, omitted, bytes })
+}
+
+// Children are collapsed before their parent, so a single step folds a whole chain; the columns stay the outermost directory's.
+fn collapse_chain(node: &mut TreeNode) {
+    let [child] = node.children.as_slice() else { return };
+    if node.omitted > 0 || !node.info.is_dir || !child.info.is_dir || !node.info.target.is_empty() || !child.info.target.is_empty() { return; }
+    let child = node.children.pop().unwrap();
+    node.info.name = format!("{}/{}", node.info.name, child.info.name);
+    node.children = child.children;
+    node.omitted = child.omitted;
+    node.count = child.count;
+    node.merged += 1 + child.merged;
 }
 
 // --- FLAT VIEW PRINTING ---
 
-fn print_simple_view(files: &[FileInfo], git_cache: &Option<GitStatusCache>) -> Result<()> {
-    let mut stdout = stdout();
+// The entries --limit keeps, and how many it leaves out.
+fn limit_entries<'a, T>(entries: &'a [T], args: &Args) -> (&'a [T], usize) {
+    let shown = &entries[..args.limit.map_or(entries.len(), |limit| limit.min(entries.len()))];
+    (shown, entries.len() - shown.len())
+}
+
+fn print_more(lead: &str, omitted: usize, args: &Args, out: &mut impl Write) -> Result<()> {
+    if omitted == 0 { return Ok(()); }
+    let color = args.use_color();
+    queue!(out, Print(lead), SetColor(Color::DarkGrey, color), Print(format!("... and {} more\n", omitted)), ResetStyle(color))
+}
+
+fn print_listing(files: &[FileInfo], args: &Args, git_cache: &Option<GitStatusCache>, out: &mut impl Write) -> Result<()> {
+    let groups = if args.sections {
+        let (dirs, others): (Vec<FileInfo>, Vec<FileInfo>) = files.iter().cloned().partition(|f| f.is_dir);
+        vec![("Directories:".to_string(), dirs), ("Files:".to_string(), others)]
+    } else if args.group_by_extension {
+        extension_groups(files, args)
+    } else {
+        return print_section(files, args, git_cache, out);
+    };
+    let mut first = true;
+    for (heading, section) in groups.iter().filter(|(_, section)| !section.is_empty()) {
+        if !first { writeln!(out)?; }
+        first = false;
+        writeln!(out, "{}", heading)?;
+        print_section(section, args, git_cache, out)?;
+    }
+    Ok(())
+}
+
+// Each group keeps the listing's order; the directories' group goes first or last as --group-dirs says.
+fn extension_groups(files: &[FileInfo], args: &Args) -> Vec<(String, Vec<FileInfo>)> {
+    let (mut dirs, mut extensionless, mut by_extension) = (Vec::new(), Vec::new(), std::collections::BTreeMap::<String, Vec<FileInfo>>::new());
     for file in files {
-        let (git_char, git_color) = git_cache.as_ref().and_then(|cache| file.path.canonicalize().ok().and_then(|p| cache.get(&p))).unwrap_or((' ', Color::Reset));
-        let file_name = file.path.file_name().unwrap().to_string_lossy();
-        let name_color = if git_char != ' ' { git_color } else { Color::White };
-        let dir_color = if git_char != ' ' { git_color } else { Color::Blue };
-        let icon = if file.is_dir { " " } else { get_icon_for_file(&file_name) };
-        execute!(stdout, Print(format!("{} ", git_char.with(git_color))),
-            SetForegroundColor(if file.is_dir { dir_color } else { name_color }), Print(icon),
-            Print(format!("{}{}\n", file_name, if file.is_dir { "/" } else { "" })), ResetColor)?;
+        match file.path.extension() {
+            _ if file.is_dir => dirs.push(file.clone()),
+            Some(ext) => by_extension.entry(ext.to_string_lossy().to_lowercase()).or_default().push(file.clone()),
+            None => extensionless.push(file.clone()),
+        }
+    }
+    let rule = if args.ascii { "--" } else { "──" };
+    let heading = |label: &str| format!("{} {} {}", rule, label, rule);
+    let mut groups: Vec<_> = by_extension.into_iter().map(|(ext, files)| (heading(&ext), files)).collect();
+    groups.push((heading("(none)"), extensionless));
+    let dirs = (heading("directories"), dirs);
+    if args.group_dirs == GroupDirs::Last { groups.push(dirs); } else { groups.insert(0, dirs); }
+    groups
+}
+
+fn print_section(files: &[FileInfo], args: &Args, git_cache: &Option<GitStatusCache>, out: &mut impl Write) -> Result<()> {
+    if args.long { print_long_view(files, args, git_cache, out) } else { print_simple_view(files, args, git_cache, out) }
+}
+
+fn print_recursive_view(path: &Path, depth: usize, args: &Args, git_cache: &Option<GitStatusCache>, out: &mut impl Write) -> Result<()> {
+    let files = with_dot_entries(path, get_entries(path, args)?, args)?;
+    if depth > 0 { writeln!(out, "\n{}:", path.display())?; }
+    if args.header_total { print_header_total(&files, args, out)?; }
+    let (shown, omitted) = limit_entries(&files, args);
+    print_listing(shown, args, git_cache, out)?;
+    print_more("", omitted, args, out)?;
+    if args.total { print_dir_total(&files, args, out)?; }
+    for dir in shown.iter().filter(|f| !is_dot_entry(f) && descends_into(f, args) && depth + 1 < args.depth) {
+        print_recursive_view(&dir.path, depth + 1, args, git_cache, out)?;
+    }
+    Ok(())
+}
+
+// Directories only carry a size when --calculate-sizes walked them, so the sum covers exactly what the listing shows,
+// symlinks aside.
+fn print_header_total(files: &[FileInfo], args: &Args, out: &mut impl Write) -> Result<()> {
+    let color = args.use_color();
+    let size = files.iter().map(counted_size).sum();
+    queue!(out, SetColor(Color::DarkGrey, color), Print(format!("Total size: {}\n", args.format_size(size))), ResetStyle(color))
+}
+
+fn print_du(root: &Path, args: &Args, out: &mut impl Write) -> Result<()> {
+    let mut dirs = Vec::new();
+    let size = collect_du_dirs(root, 0, args, &mut dirs)?;
+    dirs.sort_by(|(a_size, a), (b_size, b)| b_size.cmp(a_size).then_with(|| a.cmp(b)));
+    if args.reverse { dirs.reverse(); }
+    let color = args.use_color();
+    let total = args.format_size(if has_entry_filters(args) { calculate_dir_size(root, args) } else { size });
+    let sizes: Vec<String> = dirs.iter().map(|&(size, _)| args.format_size(size)).collect();
+    let width = sizes.iter().chain([&total]).map(|s| s.width()).max().unwrap_or(0);
+    for (size, (_, dir)) in sizes.iter().zip(&dirs) {
+        let name = quote_name(&dir.strip_prefix(root).unwrap_or(dir).to_string_lossy(), args);
+        queue!(out, Print(format!("{}  ", pad(size, width, true))), SetColor(Color::Blue, color), Print(name), ResetStyle(color), Print("\n"))?;
+    }
+    queue!(out, SetColor(Color::Green, color), Print(format!("{}  total\n", pad(&total, width, true))), ResetStyle(color))
+}
+
+// Returns the size of `dir`. As in the tree view, a subdirectory's size is summed from the walk below it instead
+// of walking it again, unless that walk is cut short by --depth or filters hide files from it.
+fn collect_du_dirs(dir: &Path, depth: usize, args: &Args, found: &mut Vec<(u64, PathBuf)>) -> Result<u64> {
+    let mut size = 0;
+    for file in read_entries(dir, args, false)? {
+        if !file.is_dir { size += counted_size(&file); continue; }
+        if crosses_file_system(&file.path, args) { continue; }
+        let descended = descends_into(&file, args) && depth + 1 < args.depth;
+        let below = if descended { collect_du_dirs(&file.path, depth + 1, args, found)? } else { 0 };
+        let dir_size = if descended && !has_entry_filters(args) { below } else { calculate_dir_size(&file.path, args) };
+        if file.link_target.is_none() { size += dir_size; }
+        found.push((dir_size, file.path));
+    }
+    Ok(size)
+}
+
+fn print_dir_total(files: &[FileInfo], args: &Args, out: &mut impl Write) -> Result<()> {
+    let color = args.use_color();
+    let (count, size) = files.iter().filter(|f| !f.is_dir).fold((0, 0), |(n, sz), f| (n + 1, sz + counted_size(f)));
+    queue!(out, SetColor(Color::Green, color),
+        Print(format!("total: {} {}, {}\n", count, if count == 1 { "file" } else { "files" }, args.format_size(size))),
+        ResetStyle(color))
+}
+
+fn print_simple_view(files: &[FileInfo], args: &Args, git_cache: &Option<GitStatusCache>, out: &mut impl Write) -> Result<()> {
+    let color = args.use_color();
+    let mut infos = Vec::new();
+    for file in files { infos.extend(skip_unreadable(build_display_info(file, args, git_cache), &file.path, args)?); }
+    if args.null {
+        for file in files { out.write_all(raw_name(file, args)?.as_encoded_bytes())?; out.write_all(b"\0")?; }
+        return Ok(());
+    }
+    if !args.oneline && (args.grid || stdout().is_tty()) {
+        return print_grid(&infos, terminal_width(args), color, out);
+    }
+    let inode_width = infos.iter().map(|info| info.inode.len()).max().unwrap_or(0);
+    for info in &infos {
+        print_simple_entry(info, inode_width, color, out)?;
+        queue!(out, Print("\n"))?;
+    }
+    Ok(())
+}
+
+// The name as the file system has it, without quoting or lossy conversion; operands keep the path they were given as.
+fn raw_name(file: &FileInfo, args: &Args) -> Result<std::ffi::OsString> {
+    if args.full_path { return Ok(std::path::absolute(&file.path)?.into_os_string()); }
+    if is_dot_entry(file) { return Ok(file.name.clone().into()); }
+    let is_operand = file.name == file.path.to_string_lossy();
+    Ok(if is_operand { file.path.clone().into_os_string() } else { file.path.file_name().unwrap_or(file.path.as_os_str()).to_os_string() })
+}
+
+fn print_simple_entry(info: &DisplayInfo, inode_width: usize, color: bool, out: &mut impl Write) -> Result<()> {
+    if inode_width > 0 { queue!(out, Print(format!("{:>width$} ", info.inode, width = inode_width)))?; }
+    queue!(out, Print(git_column(&info.git)),
+        SetColor(info.name_color, color), Print(&info.icon),
+        PrintName(info), ResetStyle(color))
+}
+
+fn simple_entry_width(info: &DisplayInfo, inode_width: usize) -> usize {
+    (if inode_width > 0 { inode_width + 1 } else { 0 }) + visible_width(&git_column(&info.git)) + info.icon.chars().count() + info.name.width() + info.suffix.width()
+}
+
+// The status column and the space after it, or nothing at all when --plain has no statuses to show.
+fn git_column(git: &str) -> String {
+    if git.is_empty() { String::new() } else { format!("{} ", git) }
+}
+
+fn terminal_width(args: &Args) -> usize {
+    args.width.unwrap_or_else(|| crossterm::terminal::size().ok().map(|(w, _)| w as usize).filter(|&w| w > 0).unwrap_or(80))
+}
+
+// Column-major layout like GNU ls: entries run down each column before moving to the next.
+fn print_grid(infos: &[DisplayInfo], width: usize, color: bool, out: &mut impl Write) -> Result<()> {
+    let inode_width = infos.iter().map(|info| info.inode.len()).max().unwrap_or(0);
+    let cell_width = infos.iter().map(|info| simple_entry_width(info, inode_width)).max().unwrap_or(0) + 2;
+    let cols = if width == 0 { infos.len() } else { width / cell_width }.max(1);
+    let rows = infos.len().div_ceil(cols);
+    for row in 0..rows {
+        for col in 0..cols {
+            let Some(info) = infos.get(col * rows + row) else { break };
+            print_simple_entry(info, inode_width, color, out)?;
+            if (col + 1) * rows + row < infos.len() {
+                queue!(out, Print(" ".repeat(cell_width - simple_entry_width(info, inode_width))))?;
+            }
+        }
+        queue!(out, Print("\n"))?;
     }
     Ok(())
 }
 
-fn print_long_view(files: &[FileInfo], git_cache: &Option<GitStatusCache>) -> Result<()> {
+fn print_long_header(widths: &mut ColumnWidths, args: &Args, out: &mut impl Write) -> Result<()> {
+    if args.no_header { return Ok(()); }
+    let color = args.use_color();
+    if widths.inode > 0 {
+        widths.inode = widths.inode.max("Inode".len());
+        queue!(out, SetColor(Color::Green, color), Print(format!("{:>width$} ", "Inode", width = widths.inode)))?;
+    }
+    let size_label = format!("Size{}", sort_indicator(args, &[SortKey::Size]));
+    widths.permissions = widths.permissions.max("Permissions".len());
+    if widths.owner > 0 { widths.owner = widths.owner.max("Owner".len()); }
+    if widths.group > 0 { widths.group = widths.group.max("Group".len()); }
+    if widths.links > 0 { widths.links = widths.links.max("Links".len()); }
+    let time_label = match args.time {
+        TimeField::Modified => format!("Last Modified{}", sort_indicator(args, &[SortKey::Time])),
+        TimeField::Created => format!("Created{}", sort_indicator(args, &[SortKey::Created])),
+        TimeField::Accessed => "Last Accessed".to_string(),
+    };
+    let rule = if args.ascii { "-" } else { "─" };
+    widths.size = widths.size.max(size_label.chars().count());
+    widths.time = widths.time.max(time_label.chars().count());
+    queue!(out, SetColor(Color::Green, color),
+        Print(format!("{:<width$} ", "Permissions", width = widths.permissions)),
+        Print(if widths.links > 0 { format!("{:>width$} ", "Links", width = widths.links) } else { String::new() }),
+        Print(if widths.owner > 0 { format!("{:<width$} ", "Owner", width = widths.owner) } else { String::new() }),
+        Print(if widths.group > 0 { format!("{:<width$} ", "Group", width = widths.group) } else { String::new() }), Print(" "),
+        Print(format!("{:>width$} ", size_label, width = widths.size)),
+        Print(format!("{:<width$} ", time_label, width = widths.time)), Print("Git "),
+        Print(format!("Name{}\n", sort_indicator(args, &[SortKey::Name, SortKey::Ext, SortKey::Version]))),
+        Print(if widths.inode > 0 { format!("{} ", "-".repeat(widths.inode)) } else { String::new() }),
+        Print(format!("{} ", "-".repeat(widths.permissions))),
+        Print(if widths.links > 0 { format!("{} ", "-".repeat(widths.links)) } else { String::new() }),
+        Print(if widths.owner > 0 { format!("{} ", rule.repeat(widths.owner)) } else { String::new() }),
+        Print(if widths.group > 0 { format!("{} ", rule.repeat(widths.group)) } else { String::new() }), Print(" "),
+        Print(format!("{} ", rule.repeat(widths.size))), Print(format!("{} ", "-".repeat(widths.time))), Print("--- "), Print("----\n"), ResetStyle(color))
+}
+
+fn update_widths(widths: &mut ColumnWidths, info: &DisplayInfo) {
+    widths.inode = widths.inode.max(info.inode.len());
+    widths.permissions = widths.permissions.max(info.permissions.len());
+    widths.links = widths.links.max(info.links.len());
+    widths.owner = widths.owner.max(info.owner.width());
+    widths.group = widths.group.max(info.group.width());
+    widths.size = widths.size.max(info.size.width());
+    widths.time = widths.time.max(info.time.width());
+    widths.git = widths.git.max(visible_width(&info.git));
+}
+
+// Green below a megabyte, yellow below a gigabyte, red from there on.
+fn size_heat(bytes: u64) -> Color {
+    if bytes < 1_000_000 { Color::Green } else if bytes < 1_000_000_000 { Color::Yellow } else { Color::Red }
+}
+
+// Bright for the last day, plain for the last month, dim beyond that.
+fn age_color(age: chrono::TimeDelta) -> Color {
+    if age.num_days() < 1 { Color::White } else if age.num_days() < 30 { Color::Grey } else { Color::DarkGrey }
+}
+
+fn visible_width(s: &str) -> usize {
+    let mut in_escape = false;
+    s.chars().filter(|&c| {
+        if in_escape { in_escape = !c.is_ascii_alphabetic(); false }
+        else if c == '\x1b' { in_escape = true; false }
+        else { true }
+    }).count()
+}
+
+// How far print_long_columns moves the cursor, so lines without an entry can line up with the names.
+fn long_columns_width(widths: &ColumnWidths) -> usize {
+    let optional = |width: usize| if width > 0 { width + 1 } else { 0 };
+    optional(widths.inode) + widths.permissions + 1 + optional(widths.links) + optional(widths.owner) + optional(widths.group) + 1 + widths.size + 1 + widths.time + 1 + if widths.git > 0 { widths.git + 2 } else { 1 }
+}
+
+fn print_long_columns(info: &DisplayInfo, widths: &ColumnWidths, out: &mut impl Write) -> Result<()> {
+    if widths.inode > 0 { queue!(out, Print(format!("{:>width$} ", info.inode, width = widths.inode)))?; }
+    queue!(out,
+        Print(format!("{:<width$} ", info.permissions, width = widths.permissions)),
+        Print(if widths.links > 0 { format!("{:>width$} ", info.links, width = widths.links) } else { String::new() }),
+        Print(if widths.owner > 0 { format!("{} ", pad(&info.owner, widths.owner, false)) } else { String::new() }),
+        Print(if widths.group > 0 { format!("{} ", pad(&info.group, widths.group, false)) } else { String::new() }), Print(" "),
+        Print(match info.size_color {
+            Some(heat) => format!("{} ", pad(&info.size, widths.size, true).with(heat)),
+            None => format!("{} ", pad(&info.size, widths.size, true)),
+        }),
+        Print(match info.time_color {
+            Some(age) => format!("{} ", pad(&info.time, widths.time, false).with(age)),
+            None => format!("{} ", pad(&info.time, widths.time, false)),
+        }),
+        Print(git_column(&info.git)), Print(" "),
+    )
+}
+
+fn sort_indicator(args: &Args, column_keys: &[SortKey]) -> &'static str {
+    if !args.sort.is_some_and(|key| column_keys.contains(&key)) { "" }
+    else if args.reverse { " ▼" }
+    else { " ▲" }
+}
+
+fn print_long_view(files: &[FileInfo], args: &Args, git_cache: &Option<GitStatusCache>, out: &mut impl Write) -> Result<()> {
     if files.is_empty() { return Ok(()); }
     let mut display_infos = Vec::new();
     let mut widths = ColumnWidths::default();
     for file in files {
-        let metadata = file.path.metadata()?;
-        let (git_char, git_color) = git_cache.as_ref().and_then(|cache| file.path.canonicalize().ok().and_then(|p| cache.get(&p))).unwrap_or((' ', Color::Reset));
-        let owner = platform::get_owner(&metadata);
-        if owner.len() > widths.owner { widths.owner = owner.len(); }
-        let size = &file.display_size;
-        if size.len() > widths.size { widths.size = size.len(); }
-        let file_name_str = file.path.file_name().unwrap().to_string_lossy();
-        display_infos.push(DisplayInfo {
-            permissions: platform::format_permissions(&metadata), owner, size: size.clone(), time: file.modified_time.format("%d-%m-%Y %H:%M").to_string(),
-            git: format!("{}", git_char.with(git_color)),
-            icon: if file.is_dir { " ".to_string() } else { get_icon_for_file(&file_name_str).to_string() },
-            name: file_name_str.to_string(),
-            name_color: if git_char != ' ' { git_color } else { if file.is_dir { Color::Blue } else { Color::White } },
-            is_dir: file.is_dir,
-        });
+        let Some(info) = skip_unreadable(build_display_info(file, args, git_cache), &file.path, args)? else { continue };
+        update_widths(&mut widths, &info);
+        display_infos.push(info);
     }
 
-    let mut stdout = stdout();
-    execute!(stdout, SetForegroundColor(Color::Green),
-        Print(format!("{:<11} ", "Permissions")), Print(format!("{:<width$}  ", "Owner", width = widths.owner)),
-        Print(format!("{:>width$} ", "Size", width = widths.size)), Print("Last Modified    "), Print("Git "), Print("Name\n"),
-        Print(format!("{:<11} ", "-----------")), Print(format!("{}  ", "─".repeat(widths.owner))),
-        Print(format!("{} ", "─".repeat(widths.size))), Print("---------------- "), Print("--- "), Print("----\n"), ResetColor)?;
+    let color = args.use_color();
+    print_long_header(&mut widths, args, out)?;
 
     for info in display_infos {
-        let owner_padded = format!("{:<width$}", info.owner, width = widths.owner);
-        let size_padded = format!("{:>width$}", info.size, width = widths.size);
-        execute!(stdout, Print(format!("{:<11} ", info.permissions)), Print(format!("{}  ", owner_padded)),
-            Print(format!("{} ", size_padded)), Print(format!("{} ", info.time)), Print(format!("{}  ", info.git)),
-            SetForegroundColor(info.name_color), Print(&info.icon),
-            Print(format!("{}{}\n", info.name, if info.is_dir { "/" } else { "" })), ResetColor)?;
+        print_long_columns(&info, &widths, out)?;
+        queue!(out, SetColor(info.name_color, color), Print(&info.icon),
+            PrintName(&info), ResetStyle(color), Print(format!("{}\n", info.target)))?;
+    }
+
+    if args.total && !args.recursive {
+        let mut totals = Totals::default();
+        for file in files.iter().filter(|f| !is_dot_entry(f)) {
+            if file.is_dir { totals.dirs += 1; } else { totals.files += 1; }
+            totals.size += counted_size(file);
+        }
+        print_totals(&totals, true, args, out)?;
     }
     Ok(())
 }
 
 // --- FINAL TREE VIEW FUNCTIONS ---
 
-fn print_tree_view(args: &Args, git_cache: &Option<GitStatusCache>) -> Result<()> {
-    let nodes = build_tree_nodes(&args.path, 0, args.depth, args.all, args.calculate_sizes, git_cache)?;
-    let mut stdout = stdout();
-    println!("{}", args.path.display());
+fn print_tree_view(root: &Path, args: &Args, git_cache: &Option<GitStatusCache>, out: &mut impl Write) -> Result<()> {
+    let TreeLevel { nodes, omitted, bytes } = build_tree_nodes(root, 0, args, git_cache)?;
+    if !args.tree_reverse { writeln!(out, "{}", root.display())?; }
 
     if args.long {
         let mut widths = ColumnWidths::default();
         calculate_data_widths(&nodes, &mut widths);
-        
-        // Print Header for long tree view
-        execute!(stdout, SetForegroundColor(Color::Green),
-            Print(format!("{:<11} ", "Permissions")), Print(format!("{:<width$}  ", "Owner", width = widths.owner)),
-            Print(format!("{:>width$} ", "Size", width = widths.size)), Print("Last Modified    "), Print("Git "), Print("Name\n"),
-            Print(format!("{:<11} ", "-----------")), Print(format!("{}  ", "─".repeat(widths.owner))),
-            Print(format!("{} ", "─".repeat(widths.size))), Print("---------------- "), Print("--- "), Print("----\n"), ResetColor)?;
-        
-        print_tree_nodes_long(&nodes, "", &widths, &mut stdout)?;
+        print_long_header(&mut widths, args, out)?;
+        print_tree_nodes_long(&nodes, omitted, "", &widths, args, out)?;
     } else {
-        print_tree_nodes_simple(&nodes, "", &mut stdout)?;
+        print_tree_nodes_simple(&nodes, omitted, "", args, out)?;
     }
-    Ok(())
+    if args.tree_reverse { writeln!(out, "{}", root.display())?; }
+
+    // Like `tree`, the view always ends with the entry counts; the size is only added when asked for.
+    let mut totals = Totals { size: bytes, ..Totals::default() };
+    calculate_tree_totals(&nodes, &mut totals);
+    print_totals(&totals, args.total || args.calculate_sizes, args, out)
+
 }
 
 fn calculate_data_widths(nodes: &[TreeNode], widths: &mut ColumnWidths) {
     for node in nodes {
-        if node.info.owner.len() > widths.owner { widths.owner = node.info.owner.len(); }
-        if node.info.size.len() > widths.size { widths.size = node.info.size.len(); }
+        update_widths(widths, &node.info);
         calculate_data_widths(&node.children, widths);
     }
 }
 
-fn print_tree_nodes_long(nodes: &[TreeNode], prefix: &str, widths: &ColumnWidths, stdout: &mut std::io::Stdout) -> Result<()> {
-    let mut peekable_nodes = nodes.iter().peekable();
-    while let Some(node) = peekable_nodes.next() {
-        let is_last = peekable_nodes.peek().is_none();
-        
-        let owner_padded = format!("{:<width$}", node.info.owner, width = widths.owner);
-        let size_padded = format!("{:>width$}", node.info.size, width = widths.size);
-        
-        execute!(stdout,
-            Print(format!("{:<11} ", node.info.permissions)),
-            Print(format!("{}  ", owner_padded)),
-            Print(format!("{} ", size_padded)),
-            Print(format!("{} ", node.info.time)),
-            Print(format!("{}  ", node.info.git)),
-        )?;
+fn calculate_tree_totals(nodes: &[TreeNode], totals: &mut Totals) {
+    for node in nodes {
+        if node.info.is_dir { totals.dirs += 1 + node.merged; } else { totals.files += 1; }
+        calculate_tree_totals(&node.children, totals);
+    }
+}
+
+fn print_totals(totals: &Totals, with_size: bool, args: &Args, out: &mut impl Write) -> Result<()> {
+    let color = args.use_color();
+    let size = if with_size { format!(", {} total", args.format_size(totals.size)) } else { String::new() };
+    queue!(out, SetColor(Color::Green, color),
+        Print(format!("\n{} {}, {} {}{}\n",
+            totals.dirs, if totals.dirs == 1 { "directory" } else { "directories" },
+            totals.files, if totals.files == 1 { "file" } else { "files" }, size)),
+        ResetStyle(color))
+}
+
+// The connector in front of an entry, and the indent its children add under it (above it with --tree-reverse).
+fn tree_connectors(is_last: bool, args: &Args) -> (&'static str, &'static str) {
+    match (is_last, args.ascii) {
+        (true, false) => (if args.tree_reverse { "┌── " } else { "└── " }, "    "),
+        (false, false) => ("├── ", "│   "),
+        (true, true) => (if args.tree_reverse { ",-- " } else { "`-- " }, "    "),
+        (false, true) => ("|-- ", "|   "),
+    }
+}
+
+// Siblings in printing order, each with whether it is the last line of its directory.
+fn tree_order<'a>(nodes: &'a [TreeNode], omitted: usize, args: &Args) -> Vec<(bool, &'a TreeNode)> {
+    let mut order: Vec<_> = nodes.iter().enumerate().map(|(i, node)| (i + 1 == nodes.len() && omitted == 0, node)).collect();
+    if args.tree_reverse { order.reverse(); }
+    order
+}
+
+fn print_tree_nodes_long(nodes: &[TreeNode], omitted: usize, prefix: &str, widths: &ColumnWidths, args: &Args, out: &mut impl Write) -> Result<()> {
+    let color = args.use_color();
+    let more_lead = format!("{}{}{}", " ".repeat(long_columns_width(widths)), prefix, tree_connectors(true, args).0);
+    if args.tree_reverse { print_more(&more_lead, omitted, args, out)?; }
+    for (is_last, node) in tree_order(nodes, omitted, args) {
+        let (branch, indent) = tree_connectors(is_last, args);
+        let new_prefix = format!("{}{}", prefix, indent);
+        if args.tree_reverse { print_tree_nodes_long(&node.children, node.omitted, &new_prefix, widths, args, out)?; }
+        print_long_columns(&node.info, widths, out)?;
 
-        let tree_prefix = format!("{}{}", prefix, if is_last { "└── " } else { "├── " });
-        execute!(stdout,
+        let tree_prefix = format!("{}{}", prefix, branch);
+        queue!(out,
             Print(tree_prefix),
-            SetForegroundColor(node.info.name_color),
+            SetColor(node.info.name_color, color),
             Print(&node.info.icon),
-            Print(format!("{}{}\n", node.info.name, if node.info.is_dir { "/" } else { "" })),
-            ResetColor,
+            PrintName(&node.info),
+            ResetStyle(color),
+            PrintCount(node.count, color),
+            Print(format!("{}\n", node.info.target)),
         )?;
 
-        if !node.children.is_empty() {
-            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
-            print_tree_nodes_long(&node.children, &new_prefix, widths, stdout)?;
-        }
+        if !args.tree_reverse { print_tree_nodes_long(&node.children, node.omitted, &new_prefix, widths, args, out)?; }
     }
+    if !args.tree_reverse { print_more(&more_lead, omitted, args, out)?; }
     Ok(())
 }
 
-fn print_tree_nodes_simple(nodes: &[TreeNode], prefix: &str, stdout: &mut std::io::Stdout) -> Result<()> {
-    let mut peekable_nodes = nodes.iter().peekable();
-    while let Some(node) = peekable_nodes.next() {
-        let is_last = peekable_nodes.peek().is_none();
-        let tree_prefix = format!("{}{}", prefix, if is_last { "└── " } else { "├── " });
+fn print_tree_nodes_simple(nodes: &[TreeNode], omitted: usize, prefix: &str, args: &Args, out: &mut impl Write) -> Result<()> {
+    let color = args.use_color();
+    let more_lead = format!("{}{}", prefix, tree_connectors(true, args).0);
+    if args.tree_reverse { print_more(&more_lead, omitted, args, out)?; }
+    for (is_last, node) in tree_order(nodes, omitted, args) {
+        let (branch, indent) = tree_connectors(is_last, args);
+        let new_prefix = format!("{}{}", prefix, indent);
+        if args.tree_reverse { print_tree_nodes_simple(&node.children, node.omitted, &new_prefix, args, out)?; }
+        let tree_prefix = format!("{}{}", prefix, branch);
 
-        execute!(stdout,
+        queue!(out,
             Print(tree_prefix),
-            Print(format!("{} ", node.info.git)),
-            SetForegroundColor(node.info.name_color),
+            Print(git_column(&node.info.git)),
+            SetColor(node.info.name_color, color),
             Print(&node.info.icon),
-            Print(format!("{}{}\n", node.info.name, if node.info.is_dir { "/" } else { "" })),
-            ResetColor,
+            PrintName(&node.info),
+            ResetStyle(color),
+            PrintCount(node.count, color),
+            Print("\n"),
         )?;
 
-        if !node.children.is_empty() {
-            let new_prefix = format!("{}{}", prefix, if is_last { "    " } else { "│   " });
-            print_tree_nodes_simple(&node.children, &new_prefix, stdout)?;
+        if !args.tree_reverse { print_tree_nodes_simple(&node.children, node.omitted, &new_prefix, args, out)?; }
+    }
+    if !args.tree_reverse { print_more(&more_lead, omitted, args, out)?; }
+    Ok(())
+}
+
+// --- JSON OUTPUT ---
+// All operands go into a single array: file operands as entries, directory operands by their contents.
+fn print_json(files: &[FileInfo], dirs: &[PathBuf], args: &Args, out: &mut impl Write) -> Result<()> {
+    let mut objects = Vec::new();
+    write_json_objects(files, 0, &args.for_operands(), &operand_git_cache(files, args), &mut objects)?;
+    for dir in dirs {
+        write_json_objects(limit_entries(&get_entries(dir, args)?, args).0, 0, args, &build_git_cache(dir, args), &mut objects)?;
+    }
+    writeln!(out, "[{}]", objects.join(","))
+}
+
+fn write_json_objects(files: &[FileInfo], depth: usize, args: &Args, git_cache: &Option<GitStatusCache>, objects: &mut Vec<String>) -> Result<()> {
+    use std::fmt::Write as _;
+    for file in files {
+        let mut out = String::new();
+        let (metadata, _) = entry_metadata(file, args)?;
+        let name = file.path.file_name().unwrap().to_string_lossy();
+        let git = git_code(file, git_cache).map_or_else(|| "null".to_string(), |code| json_string(&code));
+        let _ = write!(out, "{{\"path\":{},\"name\":{},\"is_dir\":{},\"size_bytes\":{},\"permissions\":{},\"owner\":{},\"group\":{},\"modified\":{},\"git\":{}",
+            json_string(&file.path.to_string_lossy()), json_string(&name), file.is_dir, file.size_bytes,
+            json_string(&format_permissions(&file.path, &metadata, args)), json_string(&platform::get_owner(&file.path, &metadata, args.numeric_uid_gid)),
+            json_string(&platform::get_group(&file.path, &metadata, args.numeric_uid_gid)),
+            json_string(&file.modified_time.to_rfc3339()), git);
+        if file.is_dir && (args.tree || args.recursive) {
+            let mut children = Vec::new();
+            if descends_into(file, args) && depth + 1 < args.depth {
+                write_json_objects(limit_entries(&get_entries(&file.path, args)?, args).0, depth + 1, args, git_cache, &mut children)?;
+            }
+            let _ = write!(out, ",\"children\":[{}]", children.join(","));
         }
+        out.push('}');
+        objects.push(out);
     }
     Ok(())
 }
 
-fn get_icon_for_file(file_name: &str) -> &str {
-    if file_name.ends_with(".rs") { " " }
-    else if file_name.ends_with(".md") { " " }
-    else if file_name.ends_with(".toml") { " " }
-    else if file_name == "Cargo.lock" { " " }
-    else if file_name.starts_with(".git") { " " }
-    else { " " }
-}
\ No newline at end of file
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// --- CSV OUTPUT ---
+// Names are relative to the directory given when it is the only operand, and paths as given otherwise.
+fn print_csv(files: &[FileInfo], dirs: &[PathBuf], args: &Args, out: &mut impl Write) -> Result<()> {
+    let mut rows = String::from("name,type,size_bytes,permissions,owner,group,modified,git\n");
+    write_csv_rows(files, Path::new(""), 0, &args.for_operands(), &operand_git_cache(files, args), &mut rows)?;
+    for dir in dirs {
+        let root = if args.paths.len() == 1 { dir.as_path() } else { Path::new("") };
+        write_csv_rows(limit_entries(&get_entries(dir, args)?, args).0, root, 0, args, &build_git_cache(dir, args), &mut rows)?;
+    }
+    out.write_all(rows.as_bytes())
+}
+
+fn write_csv_rows(entries: &[FileInfo], root: &Path, depth: usize, args: &Args, git_cache: &Option<GitStatusCache>, out: &mut String) -> Result<()> {
+    for file in entries {
+        let (metadata, _) = entry_metadata(file, args)?;
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_dir() { "dir" } else if file_type.is_symlink() { "symlink" } else if file_type.is_file() { "file" } else { "other" };
+        let name = file.path.strip_prefix(root).unwrap_or(&file.path).to_string_lossy();
+        let git = git_code(file, git_cache).unwrap_or_default();
+        let fields = [csv_field(&name), kind.to_string(), file.size_bytes.to_string(), csv_field(&format_permissions(&file.path, &metadata, args)),
+            csv_field(&platform::get_owner(&file.path, &metadata, args.numeric_uid_gid)),
+            csv_field(&platform::get_group(&file.path, &metadata, args.numeric_uid_gid)), file.modified_time.to_rfc3339(), git];
+        out.push_str(&fields.join(","));
+        out.push('\n');
+    }
+    if args.recursive {
+        for dir in entries.iter().filter(|f| descends_into(f, args) && depth + 1 < args.depth) {
+            write_csv_rows(limit_entries(&get_entries(&dir.path, args)?, args).0, root, depth + 1, args, git_cache, out)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_markdown(files: &[FileInfo], dirs: &[PathBuf], args: &Args, out: &mut impl Write) -> Result<()> {
+    let mut table = String::from("| Name | Size | Modified |\n| --- | ---: | --- |\n");
+    let mut push_rows = |entries: &[FileInfo], root: &Path| for file in entries {
+        let name = markdown_escape(&file.path.strip_prefix(root).unwrap_or(&file.path).to_string_lossy());
+        let suffix = if file.is_dir { "/" } else { "" };
+        table.push_str(&format!("| {}{} | {} | {} |\n", name, suffix, file.display_size, format_time(&file.modified_time, args)));
+    };
+    push_rows(files, Path::new(""));
+    for dir in dirs {
+        let root = if args.paths.len() == 1 { dir.as_path() } else { Path::new("") };
+        push_rows(limit_entries(&get_entries(dir, args)?, args).0, root);
+    }
+    out.write_all(table.as_bytes())
+}
+// Returns whether any path could not be accessed.
+fn print_stats(args: &Args, out: &mut impl Write) -> Result<bool> {
+    let mut failed = false;
+    for (i, path) in args.paths.iter().enumerate() {
+        let entry = path.symlink_metadata().and_then(|metadata| file_info(path.clone(), metadata, args, args.calculate_sizes));
+        let file = match entry {
+            Ok(file) => file,
+            Err(e) => { eprintln!("ls-pro: cannot stat '{}': {}", path.display(), e); failed = true; continue; }
+        };
+        if i > 0 { writeln!(out)?; }
+        print_stat(&file, args, out)?;
+    }
+    Ok(failed)
+}
+
+fn print_stat(file: &FileInfo, args: &Args, out: &mut impl Write) -> Result<()> {
+    let (metadata, broken) = entry_metadata(file, args)?;
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_dir() { "directory" } else if file_type.is_symlink() { "symbolic link" } else if file_type.is_file() { "regular file" } else { "special file" };
+    let icon = get_icon(&file.name, file.is_dir, args);
+    let full_path = std::path::absolute(&file.path).unwrap_or_else(|_| file.path.clone());
+    let human = if args.size_format == SizeFormat::Bytes { format_size(file.size_bytes, DECIMAL) } else { args.format_size(file.size_bytes) };
+    let permissions = format_permissions(&file.path, &metadata, args);
+    let time = |t: Option<DateTime<Local>>| t.map_or_else(|| "-".to_string(), |t| format_time(&t, args));
+    let dir = file.path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let git = match GitStatusCache::new(dir).ok().flatten() {
+        None => "not in a repository".to_string(),
+        cache => git_code(file, &cache).unwrap_or_else(|| "clean".to_string()),
+    };
+    let mut fields = vec![
+        ("File", if icon.is_empty() { full_path.display().to_string() } else { format!("{} {}", icon, full_path.display()) }),
+        ("Type", kind.to_string()),
+    ];
+    if let Some(target) = &file.link_target { fields.push(("Target", format!("{}{}", target.display(), if broken { " (broken)" } else { "" }))); }
+    fields.extend([
+        ("Size", format!("{} bytes ({})", group_thousands(file.size_bytes), human)),
+        ("Permissions", platform::format_octal_mode(&metadata).map_or(permissions.clone(), |octal| format!("{} ({})", octal, permissions))),
+        ("Owner", platform::get_owner(&file.path, &metadata, args.numeric_uid_gid)),
+        ("Group", platform::get_group(&file.path, &metadata, args.numeric_uid_gid)),
+        ("Inode", platform::get_inode(&file.path, &metadata).map_or("-".to_string(), |n| n.to_string())),
+        ("Links", platform::get_link_count(&file.path, &metadata).map_or("-".to_string(), |n| n.to_string())),
+        ("Modified", time(Some(file.modified_time))),
+        ("Accessed", time(metadata.accessed().ok().map(DateTime::from))),
+        ("Created", time(file.created_time)),
+        ("Git", git),
+    ]);
+    let color = args.use_color();
+    for (key, value) in fields.into_iter().filter(|(_, value)| !value.is_empty()) {
+        queue!(out, SetColor(Color::DarkGrey, color), Print(format!("{:>12}: ", key)), ResetStyle(color), Print(value), Print("\n"))?;
+    }
+    Ok(())
+}
+
+// Backslash-escapes what GFM would read as table or inline markup, so names like `__init__.py` come out as written.
+fn markdown_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' | '|' | '*' | '_' | '`' | '[' | ']' | '<' | '>' => { escaped.push('\\'); escaped.push(c); }
+            '\n' | '\r' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) { format!("\"{}\"", s.replace('"', "\"\"")) } else { s.to_string() }
+}
+
+// --- ICONS ---
+fn get_icon<'a>(file_name: &str, is_dir: bool, args: &'a Args) -> &'a str {
+    if !args.use_icons() { "" }
+    else if is_dir { args.icon_map.dir_icon(file_name) }
+    else { args.icon_map.file_icon(file_name) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn natural_sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|s| s.to_string()).collect();
+        names.sort_by(|a, b| natural_cmp(a.as_bytes(), b.as_bytes()));
+        names
+    }
+
+    #[test]
+    fn natural_order_compares_numbers_by_value() {
+        assert_eq!(natural_sorted(&["file10", "file2", "file1"]), ["file1", "file2", "file10"]);
+        assert_eq!(natural_sorted(&["v1.10.0", "v1.9.2", "v1.9.10"]), ["v1.9.2", "v1.9.10", "v1.10.0"]);
+    }
+
+    #[test]
+    fn natural_order_puts_more_leading_zeros_after() {
+        assert_eq!(natural_sorted(&["file001", "file1", "file01"]), ["file1", "file01", "file001"]);
+        assert_eq!(natural_sorted(&["file02", "file1", "file010"]), ["file1", "file02", "file010"]);
+        assert_eq!(natural_cmp(b"a0", b"a00"), std::cmp::Ordering::Less);
+    }
+
+    fn long_row(owner: &str, size: &str) -> DisplayInfo {
+        DisplayInfo {
+            inode: String::new(), permissions: "-rw-r--r--".to_string(), links: "1".to_string(), owner: owner.to_string(),
+            group: "staff".to_string(), size: size.to_string(), time: "14-10-2026 12:00".to_string(), git: String::new(),
+            size_color: None, time_color: None, icon: String::new(), name: "f".to_string(), suffix: "", name_color: Color::Reset,
+            is_dir: false, target: String::new(), highlights: Vec::new(), url: None,
+        }
+    }
+
+    #[test]
+    fn long_columns_pad_wide_owners_by_display_width() {
+        let rows = [long_row("root", "12.35 kB"), long_row("日本語", "1 B"), long_row("🚀", "0 B")];
+        let mut widths = ColumnWidths::default();
+        for row in &rows { update_widths(&mut widths, row); }
+        assert_eq!((widths.owner, widths.size), (6, 8));
+        let printed: Vec<String> = rows.iter().map(|row| {
+            let mut out = Vec::new();
+            print_long_columns(row, &widths, &mut out).unwrap();
+            String::from_utf8(out).unwrap()
+        }).collect();
+        for line in &printed {
+            assert_eq!(line.width(), long_columns_width(&widths), "{:?}", printed);
+            let group = line.find("staff").unwrap();
+            assert_eq!(line[..group].width(), printed[0][..printed[0].find("staff").unwrap()].width(), "{:?}", printed);
+        }
+    }
+
+    #[test]
+    fn natural_order_of_mixed_names() {
+        assert_eq!(natural_sorted(&["b", "a10b", "a2", "a10a", "10", "9"]), ["9", "10", "a2", "a10a", "a10b", "b"]);
+        assert_eq!(natural_sorted(&["file", "file1"]), ["file", "file1"]);
+    }
+}
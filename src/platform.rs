@@ -6,4 +6,4 @@ pub use self::unix::*;
 #[cfg(windows)]
 mod windows;
 #[cfg(windows)]
-pub use self::windows::*;
\ No newline at end of file
+pub use self::windows::*;
@@ -1,32 +1,183 @@
 use std::fs::Metadata;
-use std::os::unix::fs::{MetadataExt, PermissionsExt};
-use users::{Users, UsersCache};
+use std::path::Path;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt};
+use std::sync::Mutex;
+use users::{Groups, Users, UsersCache};
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe extern "C" {
+    fn listxattr(path: *const std::ffi::c_char, list: *mut std::ffi::c_char, size: usize) -> isize;
+    fn llistxattr(path: *const std::ffi::c_char, list: *mut std::ffi::c_char, size: usize) -> isize;
+}
+
+#[cfg(target_os = "macos")]
+const XATTR_NOFOLLOW: std::ffi::c_int = 0x0001;
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" {
+    fn listxattr(path: *const std::ffi::c_char, list: *mut std::ffi::c_char, size: usize, options: std::ffi::c_int) -> isize;
+}
 
 lazy_static::lazy_static! {
-    static ref USERS_CACHE: UsersCache = UsersCache::new();
+    static ref USERS_CACHE: Mutex<UsersCache> = Mutex::new(UsersCache::new());
 }
 
 pub fn format_permissions(metadata: &Metadata) -> String {
     let mode = metadata.permissions().mode();
     format!(
         "{}{}{}{}{}{}{}{}{}{}",
-        if metadata.is_dir() { 'd' } else { '-' },
+        file_type_char(metadata),
         if mode & 0o400 != 0 { 'r' } else { '-' },
         if mode & 0o200 != 0 { 'w' } else { '-' },
-        if mode & 0o100 != 0 { 'x' } else { '-' },
+        execute_char(mode, 0o100, 0o4000, 's'),
         if mode & 0o040 != 0 { 'r' } else { '-' },
         if mode & 0o020 != 0 { 'w' } else { '-' },
-        if mode & 0o010 != 0 { 'x' } else { '-' },
+        execute_char(mode, 0o010, 0o2000, 's'),
         if mode & 0o004 != 0 { 'r' } else { '-' },
         if mode & 0o002 != 0 { 'w' } else { '-' },
-        if mode & 0o001 != 0 { 'x' } else { '-' },
+        execute_char(mode, 0o001, 0o1000, 't'),
     )
 }
 
-pub fn get_owner(metadata: &Metadata) -> String {
-    let user = USERS_CACHE.get_user_by_uid(metadata.uid());
-    let group = USERS_CACHE.get_group_by_gid(metadata.gid());
-    let user_name = user.map_or_else(|| metadata.uid().to_string(), |u| u.name().to_string_lossy().into_owned());
-    let group_name = group.map_or_else(|| metadata.gid().to_string(), |g| g.name().to_string_lossy().into_owned());
-    format!("{} {}", user_name, group_name)
-}
\ No newline at end of file
+// The same type letters as ls, so they agree with the --classify indicators.
+fn file_type_char(metadata: &Metadata) -> char {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() { 'd' }
+    else if file_type.is_symlink() { 'l' }
+    else if file_type.is_fifo() { 'p' }
+    else if file_type.is_socket() { 's' }
+    else if file_type.is_char_device() { 'c' }
+    else if file_type.is_block_device() { 'b' }
+    else { '-' }
+}
+
+// setuid, setgid and sticky take over an execute slot, uppercased when the execute bit itself is off.
+fn execute_char(mode: u32, exec_bit: u32, special_bit: u32, special: char) -> char {
+    match (mode & exec_bit != 0, mode & special_bit != 0) {
+        (true, true) => special,
+        (false, true) => special.to_ascii_uppercase(),
+        (true, false) => 'x',
+        (false, false) => '-',
+    }
+}
+
+// `+` for an access control list beyond the mode bits, as GNU ls marks it, or else `@` for other extended attributes,
+// as BSD ls does. A symlink's own `metadata` asks about the link, any other about the file. Failures, such as a file
+// system without attribute support, count as having neither.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+pub fn attribute_marker(path: &Path, metadata: &Metadata) -> Option<char> {
+    // Linux keeps POSIX ACLs in these attributes, and only while they say more than the mode bits do.
+    const ACL_ATTRIBUTES: [&[u8]; 2] = [b"system.posix_acl_access", b"system.posix_acl_default"];
+    // SELinux labels every file, so its context would put an `@` on every row.
+    const SELINUX_ATTRIBUTE: &[u8] = b"security.selinux";
+    let list = xattr_list(path, !metadata.file_type().is_symlink())?;
+    let names: Vec<&[u8]> = list.split(|&b| b == 0).filter(|name| !name.is_empty() && *name != SELINUX_ATTRIBUTE).collect();
+    if names.iter().any(|name| ACL_ATTRIBUTES.contains(name)) { Some('+') }
+    else if names.is_empty() { None }
+    else { Some('@') }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+pub fn attribute_marker(_path: &Path, _metadata: &Metadata) -> Option<char> {
+    None
+}
+
+// The NUL-separated attribute names; the first call, without a buffer, only reports how long they are.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos"))]
+fn xattr_list(path: &Path, follow: bool) -> Option<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let list = |buffer: &mut [u8]| {
+        let (ptr, len) = (buffer.as_mut_ptr().cast(), buffer.len());
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        return unsafe { if follow { listxattr(path.as_ptr(), ptr, len) } else { llistxattr(path.as_ptr(), ptr, len) } };
+        #[cfg(target_os = "macos")]
+        return unsafe { listxattr(path.as_ptr(), ptr, len, if follow { 0 } else { XATTR_NOFOLLOW }) };
+    };
+    let size = list(&mut []);
+    if size <= 0 { return None; }
+    let mut buffer = vec![0; size as usize];
+    // A negative size means the attributes grew in between.
+    let size = usize::try_from(list(&mut buffer)).ok()?;
+    buffer.truncate(size);
+    Some(buffer)
+}
+
+pub fn format_octal_mode(metadata: &Metadata) -> Option<String> {
+    Some(format!("{:04o}", metadata.permissions().mode() & 0o7777))
+}
+
+pub fn is_executable(_path: &Path, metadata: &Metadata) -> bool {
+    metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+}
+
+pub fn get_inode(_path: &Path, metadata: &Metadata) -> Option<u64> {
+    Some(metadata.ino())
+}
+
+pub fn device_id(_path: &Path, metadata: &Metadata) -> Option<u64> {
+    Some(metadata.dev())
+}
+
+// Nothing is kept between lookups here; Windows caches the last file's information.
+pub fn forget_file_information() {}
+
+// `st_blocks` is always counted in 512-byte units, whatever the file system's block size.
+pub fn allocated_size(metadata: &Metadata) -> Option<u64> {
+    Some(metadata.blocks() * 512)
+}
+
+pub fn get_link_count(_path: &Path, metadata: &Metadata) -> Option<u64> {
+    Some(metadata.nlink())
+}
+
+pub fn special_file_suffix(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_fifo() { "|" } else if file_type.is_socket() { "=" } else { "" }
+}
+
+pub fn get_owner(_path: &Path, metadata: &Metadata, numeric: bool) -> String {
+    if numeric { return metadata.uid().to_string(); }
+    let user = USERS_CACHE.lock().unwrap().get_user_by_uid(metadata.uid());
+    user.map_or_else(|| metadata.uid().to_string(), |u| u.name().to_string_lossy().into_owned())
+}
+
+pub fn get_group(_path: &Path, metadata: &Metadata, numeric: bool) -> String {
+    if numeric { return metadata.gid().to_string(); }
+    let group = USERS_CACHE.lock().unwrap().get_group_by_gid(metadata.gid());
+    group.map_or_else(|| metadata.gid().to_string(), |g| g.name().to_string_lossy().into_owned())
+}
+#[cfg(test)]
+mod tests {
+    use super::execute_char;
+
+    fn execute_slots(mode: u32) -> String {
+        [execute_char(mode, 0o100, 0o4000, 's'), execute_char(mode, 0o010, 0o2000, 's'), execute_char(mode, 0o001, 0o1000, 't')].iter().collect()
+    }
+
+    #[test]
+    fn plain_execute_bits() {
+        assert_eq!(execute_slots(0o755), "xxx");
+        assert_eq!(execute_slots(0o644), "---");
+    }
+
+    #[test]
+    fn setuid_and_setgid() {
+        assert_eq!(execute_slots(0o4755), "sxx");
+        assert_eq!(execute_slots(0o4644), "S--");
+        assert_eq!(execute_slots(0o2755), "xsx");
+        assert_eq!(execute_slots(0o2745), "xSx");
+    }
+
+    #[test]
+    fn sticky() {
+        assert_eq!(execute_slots(0o1777), "xxt");
+        assert_eq!(execute_slots(0o1776), "xxT");
+    }
+
+    #[test]
+    fn all_special_bits_together() {
+        assert_eq!(execute_slots(0o7777), "sst");
+        assert_eq!(execute_slots(0o7666), "SST");
+    }
+}
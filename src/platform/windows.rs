@@ -1,17 +1,168 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
 use std::fs::Metadata;
+use std::os::windows::ffi::OsStrExt;
 use std::os::windows::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::ptr::{null, null_mut};
+use std::sync::Mutex;
+use windows_sys::Win32::Foundation::{CloseHandle, LocalFree, ERROR_SUCCESS, INVALID_HANDLE_VALUE};
+use windows_sys::Win32::Security::Authorization::{ConvertSidToStringSidW, GetNamedSecurityInfoW, SE_FILE_OBJECT};
+use windows_sys::Win32::Security::{GetLengthSid, LookupAccountSidW, OWNER_SECURITY_INFORMATION, PSID};
+use windows_sys::Win32::Storage::FileSystem::{
+    CreateFileW, GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION, FILE_ATTRIBUTE_DIRECTORY, FILE_ATTRIBUTE_READONLY,
+    FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE, OPEN_EXISTING,
+};
 
-const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
-const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+// Directories are limited to MAX_PATH minus room for an 8.3 file name.
+const LONG_PATH: usize = 260 - 12;
+
+lazy_static::lazy_static! {
+    // Resolved `DOMAIN\user` names keyed by the raw owner SID bytes.
+    static ref OWNER_CACHE: Mutex<HashMap<Vec<u8>, String>> = Mutex::new(HashMap::new());
+    // The inode, link count and device id of an entry are asked for one after another, so the file is only
+    // opened for the first of them. Forgotten at the start of each listing.
+    static ref LAST_INFORMATION: Mutex<Option<(PathBuf, Option<BY_HANDLE_FILE_INFORMATION>)>> = Mutex::new(None);
+}
 
 pub fn format_permissions(metadata: &Metadata) -> String {
     let attributes = metadata.file_attributes();
-    let dir = if (attributes & FILE_ATTRIBUTE_DIRECTORY) != 0 { 'd' } else { '-' };
+    let dir = if metadata.file_type().is_symlink() { 'l' } else if (attributes & FILE_ATTRIBUTE_DIRECTORY) != 0 { 'd' } else { '-' };
     let readonly = if (attributes & FILE_ATTRIBUTE_READONLY) != 0 { 'r' } else { '-' };
     let archive = if dir == 'd' { '-' } else { 'a' };
     format!("{}{}{}{}{}", dir, archive, readonly, "-", "-")
 }
 
-pub fn get_owner(_metadata: &Metadata) -> String {
-    "user".to_string()
+pub fn attribute_marker(_path: &Path, _metadata: &Metadata) -> Option<char> {
+    None
+}
+
+pub fn format_octal_mode(_metadata: &Metadata) -> Option<String> {
+    None
+}
+
+pub fn is_executable(path: &Path, metadata: &Metadata) -> bool {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+    metadata.is_file() && ["exe", "bat", "cmd", "com", "ps1"].contains(&ext.as_str())
+}
+
+// The file index plays the part of the inode number.
+pub fn get_inode(path: &Path, _metadata: &Metadata) -> Option<u64> {
+    file_information(path).map(|info| (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow))
+}
+
+// Each volume has a serial number of its own, which serves as its device id.
+pub fn device_id(path: &Path, _metadata: &Metadata) -> Option<u64> {
+    file_information(path).map(|info| u64::from(info.dwVolumeSerialNumber))
+}
+
+pub fn get_link_count(path: &Path, _metadata: &Metadata) -> Option<u64> {
+    file_information(path).map(|info| u64::from(info.nNumberOfLinks))
+}
+
+// Unlike the standard library, the raw Win32 calls do not lift the MAX_PATH limit by themselves: longer paths need
+// the `\\?\` prefix (`\\?\UNC\` for network shares), which only takes absolute paths with backslashes.
+fn wide_path(path: &Path) -> Vec<u16> {
+    let wide = |s: &OsStr| s.encode_wide().collect::<Vec<u16>>();
+    let absolute = std::path::absolute(path).map(|p| wide(p.as_os_str())).unwrap_or_default();
+    let mut name = if absolute.len() < LONG_PATH { wide(path.as_os_str()) } else {
+        let [verbatim, device, unc] = [r"\\?\", r"\\.\", r"\\"].map(|prefix| prefix.encode_utf16().collect::<Vec<u16>>());
+        if absolute.starts_with(&verbatim) || absolute.starts_with(&device) { absolute }
+        else if absolute.starts_with(&unc) { r"\\?\UNC\".encode_utf16().chain(absolute[2..].iter().copied()).collect() }
+        else { verbatim.into_iter().chain(absolute).collect() }
+    };
+    name.push(0);
+    name
+}
+
+pub fn forget_file_information() {
+    *LAST_INFORMATION.lock().unwrap() = None;
+}
+
+fn file_information(path: &Path) -> Option<BY_HANDLE_FILE_INFORMATION> {
+    let mut last = LAST_INFORMATION.lock().unwrap();
+    if let Some((last_path, info)) = last.as_ref() && last_path == path { return *info; }
+    let info = read_file_information(path);
+    *last = Some((path.to_path_buf(), info));
+    info
+}
+
+// Directories can only be opened with backup semantics; links are described themselves, not their targets.
+fn read_file_information(path: &Path) -> Option<BY_HANDLE_FILE_INFORMATION> {
+    let wide_path = wide_path(path);
+    let handle = unsafe {
+        CreateFileW(wide_path.as_ptr(), 0, FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE, null(), OPEN_EXISTING,
+            FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT, null_mut())
+    };
+    if handle == INVALID_HANDLE_VALUE { return None; }
+    let mut info = BY_HANDLE_FILE_INFORMATION::default();
+    let ok = unsafe { GetFileInformationByHandle(handle, &mut info) };
+    unsafe { CloseHandle(handle) };
+    (ok != 0).then_some(info)
+}
+
+pub fn allocated_size(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+// Windows has no FIFOs or sockets on the file system.
+pub fn special_file_suffix(_metadata: &Metadata) -> &'static str {
+    ""
+}
+
+pub fn get_owner(path: &Path, _metadata: &Metadata, numeric: bool) -> String {
+    lookup_owner(path, numeric).unwrap_or_else(|| "user".to_string())
+}
+
+// Files have no group of their own here, so the long view leaves the column out.
+pub fn get_group(_path: &Path, _metadata: &Metadata, _numeric: bool) -> String {
+    String::new()
+}
+
+fn lookup_owner(path: &Path, numeric: bool) -> Option<String> {
+    let wide_path = wide_path(path);
+    let (mut owner, mut descriptor) = (null_mut(), null_mut());
+    let status = unsafe {
+        GetNamedSecurityInfoW(wide_path.as_ptr(), SE_FILE_OBJECT, OWNER_SECURITY_INFORMATION,
+            &mut owner, null_mut(), null_mut(), null_mut(), &mut descriptor)
+    };
+    if status != ERROR_SUCCESS || owner.is_null() { return None; }
+
+    let name = if numeric { sid_string(owner) } else {
+        let sid = unsafe { std::slice::from_raw_parts(owner.cast::<u8>(), GetLengthSid(owner) as usize) }.to_vec();
+        let cached = OWNER_CACHE.lock().unwrap().get(&sid).cloned();
+        cached.or_else(|| {
+            let name = account_name(owner)?;
+            OWNER_CACHE.lock().unwrap().insert(sid, name.clone());
+            Some(name)
+        })
+    };
+    unsafe { LocalFree(descriptor) };
+    name
+}
+
+// The `S-1-5-…` form, which needs no account lookup.
+fn sid_string(sid: PSID) -> Option<String> {
+    let mut string = null_mut();
+    if unsafe { ConvertSidToStringSidW(sid, &mut string) } == 0 { return None; }
+    let len = (0..).take_while(|&i| unsafe { *string.add(i) } != 0).count();
+    let text = String::from_utf16_lossy(unsafe { std::slice::from_raw_parts(string, len) });
+    unsafe { LocalFree(string.cast()) };
+    Some(text)
+}
+
+fn account_name(sid: PSID) -> Option<String> {
+    let (mut name_len, mut domain_len, mut sid_use) = (0u32, 0u32, 0);
+    // The first call only reports the buffer sizes needed.
+    unsafe { LookupAccountSidW(null(), sid, null_mut(), &mut name_len, null_mut(), &mut domain_len, &mut sid_use) };
+    if name_len == 0 { return None; }
+    let mut name = vec![0u16; name_len as usize];
+    let mut domain = vec![0u16; domain_len.max(1) as usize];
+    let ok = unsafe {
+        LookupAccountSidW(null(), sid, name.as_mut_ptr(), &mut name_len, domain.as_mut_ptr(), &mut domain_len, &mut sid_use)
+    };
+    if ok == 0 { return None; }
+    let name = String::from_utf16_lossy(&name[..name_len as usize]);
+    let domain = String::from_utf16_lossy(&domain[..domain_len as usize]);
+    Some(if domain.is_empty() { name } else { format!("{}\\{}", domain, name) })
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+// Waiting for changes to a set of directories, told by inotify, FSEvents or ReadDirectoryChangesW as the platform
+// has it. Where none of them works, --live checks the files on a timer instead.
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+// A burst of writes is waited out until it has been quiet this long, but not for longer than the cap.
+const QUIET: Duration = Duration::from_millis(100);
+const QUIET_CAP: Duration = Duration::from_secs(1);
+
+pub struct ChangeWatcher { _watcher: notify::RecommendedWatcher, events: Receiver<notify::Result<notify::Event>> }
+
+impl ChangeWatcher {
+    // Each directory is watched on its own, so new subdirectories need a new watcher. None when no directory
+    // could be watched, say once the per-user inotify limit is reached.
+    pub fn new(dirs: &[PathBuf]) -> Option<Self> {
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender).ok()?;
+        let mut watching = false;
+        for dir in dirs { watching |= watcher.watch(dir, RecursiveMode::NonRecursive).is_ok(); }
+        watching.then_some(Self { _watcher: watcher, events })
+    }
+
+    // Blocks until something changes, then until the changes settle. Reading a directory, as the listing itself
+    // does, is no change; an error such as a lost event might hide one, so it counts as one.
+    pub fn wait(&self) {
+        loop {
+            match self.events.recv() {
+                Ok(Ok(event)) if matches!(event.kind, EventKind::Access(_)) => {}
+                Ok(_) => break,
+                Err(_) => return,
+            }
+        }
+        let start = Instant::now();
+        while start.elapsed() < QUIET_CAP && self.events.recv_timeout(QUIET).is_ok() {}
+    }
+}
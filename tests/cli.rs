@@ -0,0 +1,431 @@
+// End-to-end checks that run the built binary against small directory fixtures.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+struct Fixture { root: PathBuf }
+
+impl Fixture {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("ls-pro-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        Self { root }
+    }
+
+    // Parent directories are created along the way; a path ending in `/` makes a directory.
+    fn add(&self, path: &str, contents: &str) -> &Self {
+        let full = self.root.join(path);
+        if path.ends_with('/') { fs::create_dir_all(&full).unwrap(); return self; }
+        fs::create_dir_all(full.parent().unwrap()).unwrap();
+        fs::write(full, contents).unwrap();
+        self
+    }
+
+    fn ls(&self, args: &[&str]) -> String { self.ls_at("", args) }
+
+    fn ls_at(&self, path: &str, args: &[&str]) -> String {
+        let output = self.run(path, args);
+        assert!(output.status.success(), "ls-pro {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    // What ls-pro says on stderr when it is expected to refuse the arguments.
+    fn ls_error(&self, args: &[&str]) -> String {
+        let output = self.run("", args);
+        assert!(!output.status.success(), "ls-pro {:?} succeeded", args);
+        String::from_utf8(output.stderr).unwrap()
+    }
+
+    fn run(&self, path: &str, args: &[&str]) -> std::process::Output {
+        Command::new(env!("CARGO_BIN_EXE_ls-pro"))
+            .args(["--no-config", "--color=never", "--icons=never"]).args(args).arg(self.root.join(path))
+            .env_remove("XDG_CONFIG_HOME").output().unwrap()
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) { let _ = fs::remove_dir_all(&self.root); }
+}
+
+// Terminal columns of the fixtures' names, whose only wide characters are CJK and emoji.
+fn columns(s: &str) -> usize { s.chars().map(|c| if c as u32 >= 0x1100 { 2 } else { 1 }).sum() }
+
+#[test]
+fn grid_columns_line_up_past_wide_names() {
+    let fixture = Fixture::new("wide-grid");
+    fixture.add("0日本", "").add("1🚀", "").add("a", "").add("b", "");
+    let output = fixture.ls(&["--grid", "--width=24"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", output);
+    let starts: Vec<usize> = lines.iter().zip(["a", "b"]).map(|(line, name)| columns(&line[..line.find(name).unwrap()])).collect();
+    assert_eq!(starts[0], starts[1], "{}", output);
+}
+
+// The terminal columns where each whitespace-separated word of a line starts and ends.
+fn word_spans(line: &str) -> Vec<(usize, usize)> {
+    let (mut spans, mut column, mut start) = (Vec::new(), 0, None);
+    for c in line.chars() {
+        match (c == ' ', start) {
+            (true, Some(first)) => { spans.push((first, column)); start = None; }
+            (false, None) => start = Some(column),
+            _ => {}
+        }
+        column += columns(&c.to_string());
+    }
+    spans.extend(start.map(|first| (first, column)));
+    spans
+}
+
+#[test]
+fn long_view_owner_and_size_columns_line_up_past_wide_names() {
+    let fixture = Fixture::new("wide-long");
+    fixture.add("a", "").add("日本", "x").add("🚀", &"x".repeat(12345));
+    let output = fixture.ls(&["-l"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.len(), 5, "{}", output);
+    let header = word_spans(lines[0]);
+    for row in &lines[2..] {
+        // Permissions, links, owner, group, then the size and its unit.
+        let words = word_spans(row);
+        assert_eq!(words[2].0, header[2].0, "owner column:\n{}", output);
+        assert_eq!(words[5].1, header[4].1, "size column:\n{}", output);
+    }
+}
+
+// The names in a listing, without tree connectors, status columns or the tree's summary line.
+fn names(output: &str) -> Vec<&str> {
+    output.lines().map(|line| line.trim_start_matches(['│', '├', '└', '─', '|', '`', ',', '-', ' ']))
+        .filter(|name| !name.is_empty() && !name.ends_with(" files") && !name.ends_with(" file")).collect()
+}
+
+#[test]
+fn reverse_flips_the_order_within_the_directory_group() {
+    let fixture = Fixture::new("reverse");
+    fixture.add("a.txt", "").add("b.txt", "").add("c.txt", "").add("x/", "").add("y/", "").add("y/1", "").add("y/2", "");
+    assert_eq!(names(&fixture.ls(&[])), ["x/", "y/", "a.txt", "b.txt", "c.txt"]);
+    assert_eq!(names(&fixture.ls(&["-r"])), ["y/", "x/", "c.txt", "b.txt", "a.txt"]);
+    assert_eq!(names(&fixture.ls(&["--tree", "-r"]))[1..], ["y/", "2", "1", "x/", "c.txt", "b.txt", "a.txt"]);
+}
+
+#[test]
+fn glob_keeps_only_matching_names() {
+    let fixture = Fixture::new("glob");
+    fixture.add("main.rs", "").add("lib.rs", "").add("notes.txt", "").add("README.md", "").add(".hidden.rs", "")
+        .add("src/", "").add("src/util.rs", "").add("src/data.json", "");
+    assert_eq!(names(&fixture.ls(&["--glob=*.rs"])), ["lib.rs", "main.rs"]);
+    assert_eq!(names(&fixture.ls(&["--glob=*.rs", "--glob=*.md"])), ["README.md", "lib.rs", "main.rs"]);
+    assert_eq!(names(&fixture.ls(&["--glob=*.rs", "-A"])), [".hidden.rs", "lib.rs", "main.rs"]);
+    // The tree still goes through directories that do not match, to reach the leaves that do.
+    assert_eq!(names(&fixture.ls(&["--tree", "--glob=*.rs"]))[1..], ["src/", "util.rs", "lib.rs", "main.rs"]);
+}
+
+#[test]
+fn ignore_glob_drops_matches_and_skips_ignored_directories() {
+    let fixture = Fixture::new("ignore-glob");
+    fixture.add("keep.rs", "").add("scratch.tmp", "").add("old.bak", "").add("cache/", "").add("cache/deep.rs", "")
+        .add("src/", "").add("src/a.rs", "").add("src/b.tmp", "");
+    assert_eq!(names(&fixture.ls(&[])), ["cache/", "src/", "keep.rs", "old.bak", "scratch.tmp"]);
+    assert_eq!(names(&fixture.ls(&["--ignore-glob=*.tmp"])), ["cache/", "src/", "keep.rs", "old.bak"]);
+    assert_eq!(names(&fixture.ls(&["--ignore-glob=*.tmp", "--ignore-glob=*.bak"])), ["cache/", "src/", "keep.rs"]);
+    assert_eq!(names(&fixture.ls(&["--tree", "--ignore-glob=*.tmp", "--ignore-glob=cache"]))[1..], ["src/", "a.rs", "keep.rs", "old.bak"]);
+}
+
+// The size and name of each entry in a long listing whose other columns hold no spaces.
+fn long_sizes(output: &str) -> Vec<(String, String)> {
+    output.lines().filter(|line| line.starts_with(['d', '-', 'l']) && !line.starts_with("--")).map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        (fields[fields.len() - 1].to_string(), fields[4].to_string())
+    }).collect()
+}
+
+#[test]
+fn tree_directory_sizes_match_the_flat_view() {
+    let fixture = Fixture::new("tree-sizes");
+    fixture.add("top.txt", "12345").add("a/one", "1").add("a/b/two", "22").add("a/b/c/three", "333").add("a/b/c/four", "4444").add("z/", "");
+    let sized = ["--long", "--calculate-sizes", "--size-format=bytes"];
+    let tree = long_sizes(&fixture.ls(&[&sized[..], &["--tree"]].concat()));
+    let mut flat = long_sizes(&fixture.ls(&sized));
+    flat.extend(long_sizes(&fixture.ls_at("a", &sized)));
+    flat.extend(long_sizes(&fixture.ls_at("a/b", &sized)));
+    for dir in ["a/", "b/", "c/", "z/"] {
+        let size = |listing: &[(String, String)]| listing.iter().find(|(name, _)| name == dir).map(|(_, size)| size.clone());
+        assert_eq!(size(&tree), size(&flat), "size of {}", dir);
+    }
+    assert!(tree.contains(&("a/".to_string(), "10".to_string())), "{:?}", tree);
+}
+
+// Symlinks add nothing to any total, under a directory or beside it.
+#[cfg(unix)]
+#[test]
+fn totals_leave_out_symlinks_in_every_view() {
+    let fixture = Fixture::new("link-totals");
+    fixture.add("top.txt", "12345").add("a/one", "1");
+    std::os::unix::fs::symlink("one", fixture.root.join("a/two-link")).unwrap();
+    std::os::unix::fs::symlink("top.txt", fixture.root.join("link")).unwrap();
+    let sized = ["--long", "--calculate-sizes", "--size-format=bytes"];
+    let total = |output: String| output.lines().last().map(|line| line.rsplit(", ").next().unwrap().to_string()).unwrap();
+    assert_eq!(total(fixture.ls(&[&sized[..], &["--total"]].concat())), "6 total");
+    assert_eq!(total(fixture.ls(&[&sized[..], &["--total", "--tree"]].concat())), "6 total");
+    let header = fixture.ls(&[&sized[..], &["--header-total"]].concat());
+    assert_eq!(header.lines().next(), Some("Total size: 6"), "{}", header);
+    assert!(long_sizes(&fixture.ls(&sized)).contains(&("a/".to_string(), "1".to_string())));
+}
+
+#[cfg(unix)]
+#[test]
+fn classify_marks_each_kind_of_entry() {
+    use std::os::unix::fs::PermissionsExt;
+    let fixture = Fixture::new("classify");
+    fixture.add("dir/", "").add("plain", "").add("run.sh", "#!/bin/sh\n");
+    fs::set_permissions(fixture.root.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    std::os::unix::fs::symlink("dir", fixture.root.join("dir-link")).unwrap();
+    std::os::unix::fs::symlink("plain", fixture.root.join("file-link")).unwrap();
+    assert!(Command::new("mkfifo").arg(fixture.root.join("pipe")).status().unwrap().success());
+    let _socket = std::os::unix::net::UnixListener::bind(fixture.root.join("sock")).unwrap();
+    assert_eq!(names(&fixture.ls(&["-F"])), ["dir/", "dir-link@", "file-link@", "pipe|", "plain", "run.sh*", "sock="]);
+    assert_eq!(names(&fixture.ls(&[])), ["dir/", "dir-link", "file-link", "pipe", "plain", "run.sh", "sock"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn dangling_symlinks_do_not_stop_the_listing() {
+    let fixture = Fixture::new("dangling");
+    fixture.add("a.txt", "").add("sub/", "").add("sub/z.txt", "");
+    std::os::unix::fs::symlink("missing-target", fixture.root.join("m-link")).unwrap();
+    std::os::unix::fs::symlink("../nowhere", fixture.root.join("sub/deep-link")).unwrap();
+    assert_eq!(names(&fixture.ls(&[])), ["sub/", "a.txt", "m-link"]);
+    let long = fixture.ls(&["--long"]);
+    assert!(long.lines().any(|line| line.starts_with('l') && line.ends_with("m-link -> missing-target [broken]")), "{}", long);
+    assert_eq!(names(&fixture.ls(&["--tree"]))[1..], ["sub/", "deep-link", "z.txt", "a.txt", "m-link"]);
+    assert!(fixture.ls(&["--tree", "--long", "--calculate-sizes"]).contains("deep-link -> ../nowhere [broken]"));
+}
+
+#[test]
+fn recursive_depth_stops_at_the_given_level() {
+    let fixture = Fixture::new("depth");
+    fixture.add("one/two/three/four.txt", "").add("one/two/2.txt", "").add("one/1.txt", "");
+    let output = fixture.ls(&["--recursive", "--depth=2"]);
+    assert_eq!(names(&output).iter().filter(|name| !name.ends_with(':')).collect::<Vec<_>>(), [&"one/", &"two/", &"1.txt"]);
+    assert!(!output.contains("three"), "{}", output);
+    assert_eq!(names(&fixture.ls(&["--tree", "--depth=2"]))[1..], ["one/", "two/", "1.txt"]);
+    for view in ["--recursive", "--tree", "--du"] { assert!(fixture.ls_error(&[view, "--depth=0"]).contains("at least 1")); }
+}
+
+fn git_fixture(name: &str) -> Fixture {
+    let fixture = Fixture::new(name);
+    git2::Repository::init(&fixture.root).unwrap();
+    fixture
+}
+
+// Commits everything in the fixture as it stands.
+fn commit_all(fixture: &Fixture) {
+    let repo = git2::Repository::open(&fixture.root).unwrap();
+    let mut index = repo.index().unwrap();
+    index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = git2::Signature::now("test", "test@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "fixture", &tree, &[]).unwrap();
+}
+
+#[test]
+fn directories_are_untracked_only_when_nothing_in_them_is_tracked() {
+    let fixture = git_fixture("dir-status");
+    fixture.add("src/main.rs", "").add("lib/lib.rs", "");
+    commit_all(&fixture);
+    fixture.add("src/new.rs", "").add("lib/lib.rs", "changed").add("lib/new.rs", "").add("fresh/a.rs", "").add("fresh/b.rs", "");
+    let output = fixture.ls(&["--git"]);
+    for line in [" ? src/", " M lib/", "?? fresh/"] { assert!(output.lines().any(|l| l == line), "{:?} in\n{}", line, output); }
+}
+
+#[test]
+fn ignored_entries_are_marked_or_left_out() {
+    let fixture = git_fixture("ignored");
+    fixture.add(".gitignore", "*.log\nbuild/\n").add("app.rs", "").add("debug.log", "").add("build/out.o", "");
+    assert_eq!(names(&fixture.ls(&[])), ["app.rs"]);
+    let marked = fixture.ls(&["-A", "--git"]);
+    for name in ["build/", "debug.log"] { assert!(marked.lines().any(|line| line == format!("!! {}", name)), "{}", marked); }
+    assert!(marked.lines().any(|line| line == "?? app.rs"), "{}", marked);
+    assert_eq!(names(&fixture.ls(&["-A", "--no-ignored"])), [".git/", ".gitignore", "app.rs"]);
+}
+
+// The link count column of an entry in a long listing.
+fn long_links(output: &str, name: &str) -> Option<String> {
+    output.lines().find(|line| line.ends_with(name)).map(|line| line.split_whitespace().nth(1).unwrap().to_string())
+}
+
+#[test]
+fn long_view_counts_hard_links() {
+    let fixture = Fixture::new("links");
+    fixture.add("single", "").add("shared", "");
+    fs::hard_link(fixture.root.join("shared"), fixture.root.join("shared-2")).unwrap();
+    fs::hard_link(fixture.root.join("shared"), fixture.root.join("shared-3")).unwrap();
+    let output = fixture.ls(&["--long"]);
+    assert_eq!(long_links(&output, " single").as_deref(), Some("1"), "{}", output);
+    assert_eq!(long_links(&output, " shared").as_deref(), Some("3"), "{}", output);
+}
+
+// The listing with the fixture's root path, which changes from run to run, left out of the first line.
+fn without_root(output: &str) -> String { output.split_once('\n').map_or(String::new(), |(_, rest)| rest.to_string()) }
+
+#[test]
+fn ascii_tree_snapshot() {
+    let fixture = Fixture::new("ascii");
+    fixture.add("src/main.rs", "").add("src/util/mod.rs", "").add("Cargo.toml", "");
+    assert_eq!(without_root(&fixture.ls(&["--tree", "--ascii"])), "\
+|--   src/
+|   |--   util/
+|   |   `--   mod.rs
+|   `--   main.rs
+`--   Cargo.toml
+
+2 directories, 3 files
+");
+}
+
+#[test]
+fn prune_drops_directories_without_files() {
+    let fixture = Fixture::new("prune");
+    fixture.add("empty/", "").add("nested/deeper/emptiest/", "").add("full/inner/file.txt", "").add("top.txt", "");
+    assert_eq!(names(&fixture.ls(&["--tree"]))[1..], ["empty/", "full/", "inner/", "file.txt", "nested/", "deeper/", "emptiest/", "top.txt"]);
+    assert_eq!(names(&fixture.ls(&["--tree", "--prune"]))[1..], ["full/", "inner/", "file.txt", "top.txt"]);
+    // The directory asked for is listed even when it holds nothing.
+    assert_eq!(without_root(&fixture.ls_at("empty", &["--tree", "--prune"])), "\n0 directories, 0 files\n");
+}
+
+#[test]
+fn tree_reverse_lists_children_before_their_parents() {
+    let fixture = Fixture::new("tree-reverse");
+    fixture.add("src/main.rs", "").add("src/util/mod.rs", "").add("Cargo.toml", "");
+    let output = fixture.ls(&["--tree", "--tree-reverse", "--ascii"]);
+    let (tree, summary) = output.split_once("\n\n").unwrap();
+    assert_eq!(summary, "2 directories, 3 files\n");
+    assert_eq!(tree.lines().take(5).collect::<Vec<_>>(), [
+        ",--   Cargo.toml",
+        "|   ,--   main.rs",
+        "|   |   ,--   mod.rs",
+        "|   |--   util/",
+        "|--   src/",
+    ]);
+    assert_eq!(tree.lines().nth(5), Some(format!("{}/", fixture.root.display()).as_str()));
+}
+
+#[test]
+fn nested_gitignores_layer_in_every_view() {
+    let fixture = git_fixture("nested-ignores");
+    fixture.add(".gitignore", "*.log\n/root-only.txt\n").add("root-only.txt", "").add("keep.txt", "")
+        .add("app/.gitignore", "generated/\n!important.log\n").add("app/root-only.txt", "").add("app/main.rs", "")
+        .add("app/debug.log", "").add("app/important.log", "").add("app/generated/out.rs", "")
+        .add("app/lib/trace.log", "").add("app/lib/lib.rs", "");
+    assert_eq!(names(&fixture.ls(&["--tree"]))[1..],
+        ["app/", "lib/", "lib.rs", "important.log", "main.rs", "root-only.txt", "keep.txt"]);
+    // Starting a walk below the root still applies the root's and the parents' patterns.
+    assert_eq!(names(&fixture.ls_at("app", &[])), ["lib/", "important.log", "main.rs", "root-only.txt"]);
+    assert_eq!(names(&fixture.ls_at("app/lib", &[])), ["lib.rs"]);
+    let recursive = fixture.ls(&["--recursive"]);
+    for ignored in ["debug.log", "trace.log", "generated", "out.rs"] { assert!(!recursive.contains(ignored), "{}", recursive); }
+}
+
+#[test]
+fn group_dirs_orders_tree_siblings_like_flat_listings() {
+    let fixture = Fixture::new("group-dirs");
+    fixture.add("b.txt", "").add("d/", "").add("top/a.txt", "").add("top/c/", "").add("top/e.txt", "").add("top/b/", "");
+    for (mode, flat, nested) in [
+        ("first", ["d/", "top/", "b.txt"], ["b/", "c/", "a.txt", "e.txt"]),
+        ("last", ["b.txt", "d/", "top/"], ["a.txt", "e.txt", "b/", "c/"]),
+        ("none", ["b.txt", "d/", "top/"], ["a.txt", "b/", "c/", "e.txt"]),
+    ] {
+        let group = format!("--group-dirs={}", mode);
+        assert_eq!(names(&fixture.ls(&[&group])), flat, "{}", mode);
+        assert_eq!(names(&fixture.ls_at("top", &[&group])), nested, "{}", mode);
+        let output = fixture.ls(&["--tree", &group]);
+        let tree = &names(&output)[1..];
+        let top = tree.iter().position(|&name| name == "top/").unwrap();
+        assert_eq!(tree.iter().filter(|name| flat.contains(name)).copied().collect::<Vec<_>>(), flat, "{}", mode);
+        assert_eq!(tree[top + 1..top + 5], nested, "{}", mode);
+    }
+}
+
+#[test]
+fn numeric_ids_refuse_what_long_view_refuses() {
+    let fixture = Fixture::new("numeric-ids");
+    fixture.add("a", "");
+    for flag in ["-0", "--grid", "--du"] {
+        assert!(fixture.ls_error(&["-n", flag]).contains("cannot be used with"), "-n {}", flag);
+    }
+    let output = fixture.ls(&["-n", "--count"]);
+    assert!(output.lines().any(|line| line.ends_with(" a")), "{}", output);
+}
+
+#[test]
+fn a_file_operand_is_listed_by_itself() {
+    let fixture = Fixture::new("file-operand");
+    fixture.add("only.txt", "abc").add("sibling.txt", "").add("sub/inner.txt", "");
+    let file = fixture.root.join("only.txt").display().to_string();
+    let simple = fixture.ls_at("only.txt", &[]);
+    assert_eq!(simple.lines().map(str::trim).collect::<Vec<_>>(), [file.as_str()], "{}", simple);
+    let long = fixture.ls_at("only.txt", &["-l", "--no-header"]);
+    let rows: Vec<&str> = long.lines().collect();
+    assert_eq!(rows.len(), 1, "{}", long);
+    assert!(rows[0].starts_with('-') && rows[0].ends_with(&format!(" {}", file)), "{}", long);
+}
+
+// A directory that can be read but not searched lists its names, but none of them can be looked up. Root gets
+// past the permissions, so there is nothing to check then.
+#[cfg(unix)]
+#[test]
+fn unlookable_entries_are_reported_and_skipped() {
+    use std::os::unix::fs::PermissionsExt;
+    let fixture = Fixture::new("unlookable");
+    fixture.add("top", "").add("locked/a", "").add("locked/b", "");
+    let locked = fixture.root.join("locked");
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o644)).unwrap();
+    if fs::metadata(locked.join("a")).is_ok() {
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+        eprintln!("skipped: permissions are not enforced for this user");
+        return;
+    }
+    let (listing, strict) = (fixture.run("", &["-R"]), fixture.run("", &["-R", "--strict"]));
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    let (stdout, stderr) = (String::from_utf8_lossy(&listing.stdout), String::from_utf8_lossy(&listing.stderr));
+    assert_eq!(listing.status.code(), Some(1), "{}", stderr);
+    assert_eq!(names(&stdout)[..2], ["locked/", "top"], "{}", stdout);
+    assert_eq!(stderr.lines().filter(|line| line.contains("cannot access") && line.contains("Permission denied")).count(), 2, "{}", stderr);
+    assert_eq!(stderr.lines().last(), Some("ls-pro: 2 entries could not be read"), "{}", stderr);
+    assert!(!strict.status.success() && !String::from_utf8_lossy(&strict.stderr).contains("could not be read"), "{:?}", strict);
+}
+
+#[test]
+fn plain_output_has_nothing_before_the_names() {
+    let fixture = Fixture::new("plain");
+    fixture.add("a.txt", "").add("b.txt", "").add("dir/", "").add("dir/c.txt", "");
+    assert_eq!(fixture.ls(&["--plain", "-1"]).lines().collect::<Vec<_>>(), ["dir/", "a.txt", "b.txt"]);
+    assert_eq!(fixture.ls(&["--plain", "--grid", "--width=80"]).lines().collect::<Vec<_>>(), ["dir/   a.txt  b.txt"]);
+    assert_eq!(fixture.ls(&["--plain", "--tree"]).lines().nth(1), Some("├── dir/"));
+    assert_eq!(fixture.ls(&["-1"]).lines().next(), Some("  dir/"));
+}
+
+#[test]
+fn limit_applies_to_machine_readable_output() {
+    let fixture = Fixture::new("limit-formats");
+    fixture.add("a", "").add("b", "").add("c", "").add("d/", "").add("d/1", "").add("d/2", "").add("d/3", "");
+    assert_eq!(fixture.ls(&["--csv", "--limit=2"]).lines().count(), 1 + 2);
+    assert_eq!(fixture.ls(&["--csv", "-R", "--limit=2"]).lines().count(), 1 + 2 + 2);
+    assert_eq!(fixture.ls(&["--markdown", "--limit=2"]).lines().count(), 2 + 2);
+    let json = fixture.ls(&["--json", "--tree", "--limit=2"]);
+    assert_eq!(json.matches("\"path\":").count(), 2 + 2, "{}", json);
+}
+
+#[test]
+fn du_sizes_match_calculated_directory_sizes() {
+    let fixture = Fixture::new("du-sizes");
+    fixture.add("top", "12345").add("a/one", "1").add("a/b/two", "22").add("a/b/c/three", "333").add("z/", "");
+    let du = fixture.ls(&["--du", "--depth=3", "--size-format=bytes"]);
+    let sizes: Vec<(String, &str)> = du.lines().filter_map(|line| line.trim_start().split_once("  "))
+        .map(|(size, dir)| (dir.replace(std::path::MAIN_SEPARATOR, "/"), size)).collect();
+    assert_eq!(sizes, [("a", "6"), ("a/b", "5"), ("a/b/c", "3"), ("z", "0"), ("total", "11")].map(|(dir, size)| (dir.to_string(), size)), "{}", du);
+    let flat = long_sizes(&fixture.ls(&["--long", "--calculate-sizes", "--size-format=bytes"]));
+    assert!(flat.contains(&("a/".to_string(), "6".to_string())), "{:?}", flat);
+}